@@ -0,0 +1,90 @@
+//! Compares job throughput of the channel and work-stealing schedulers.
+//!
+//! Run with `cargo bench --bench scheduler`. Results are written to stderr.
+
+use std::{
+    hint::black_box,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use websvr::{Scheduler, ThreadPoolBuilder};
+
+const JOBS: usize = 100_000;
+const ROUNDS: usize = 5;
+
+fn main() {
+    let threads = thread::available_parallelism().map_or(4, |n| n.get());
+    eprintln!(
+        "{} workers, {} jobs per round, best of {}",
+        threads, JOBS, ROUNDS
+    );
+
+    for producers in [1, threads.max(4)] {
+        for work in [0, 1_000] {
+            for scheduler in [Scheduler::Channel, Scheduler::WorkStealing] {
+                let best = (0..ROUNDS)
+                    .map(|_| run(scheduler, threads, producers, work))
+                    .min()
+                    .unwrap();
+                let rate = JOBS as f64 / best.as_secs_f64();
+                eprintln!(
+                    "{:<14} producers={:<3} work={:<5} {:>10.2?} {:>12.0} jobs/s",
+                    format!("{:?}", scheduler),
+                    producers,
+                    work,
+                    best,
+                    rate
+                );
+            }
+        }
+    }
+}
+
+/// Runs JOBS jobs of work spin iterations each, submitted from producers threads.
+fn run(scheduler: Scheduler, threads: usize, producers: usize, work: usize) -> Duration {
+    let pool = Arc::new(
+        ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .scheduler(scheduler)
+            .build()
+            .unwrap(),
+    );
+    let remaining = Arc::new(AtomicUsize::new(JOBS));
+    let (done, finished) = mpsc::channel();
+
+    let start = Instant::now();
+    let handles: Vec<_> = (0..producers)
+        .map(|p| {
+            let pool = Arc::clone(&pool);
+            let remaining = Arc::clone(&remaining);
+            let done = done.clone();
+            thread::spawn(move || {
+                let share = JOBS / producers + usize::from(p < JOBS % producers);
+                for _ in 0..share {
+                    let remaining = Arc::clone(&remaining);
+                    let done = done.clone();
+                    pool.execute(move || {
+                        let mut x = 0u64;
+                        for i in 0..work {
+                            x = black_box(x.wrapping_add(i as u64));
+                        }
+                        black_box(x);
+                        if remaining.fetch_sub(1, Ordering::SeqCst) == 1 {
+                            done.send(()).unwrap();
+                        }
+                    });
+                }
+            })
+        })
+        .collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    finished.recv().unwrap();
+    start.elapsed()
+}
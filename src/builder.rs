@@ -0,0 +1,403 @@
+use std::{
+    fmt, io,
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    job::panic_message, EventListener, JobPanic, PanicHandler, PoolCreationError, QueuePolicy,
+    Scheduler, ThreadPool,
+};
+
+/// Configures and creates a ThreadPool.
+///
+/// Threads can be named, given a stack size, and run setup and teardown
+/// closures, e.g. to open a per-thread database connection.
+pub struct ThreadPoolBuilder {
+    /// None until set, so it can follow max_threads down.
+    min_threads: Option<usize>,
+    max_threads: usize,
+    keep_alive: Duration,
+    queue_capacity: Option<usize>,
+    queue_policy: QueuePolicy,
+    scheduler: Scheduler,
+    panic_handler: Option<PanicHandler>,
+    listener: Option<Arc<dyn EventListener>>,
+    config: WorkerConfig,
+}
+
+impl ThreadPoolBuilder {
+    /// Creates a builder with a fixed thread per available CPU, an unbounded
+    /// queue and unnamed threads.
+    pub fn new() -> ThreadPoolBuilder {
+        ThreadPoolBuilder {
+            min_threads: None,
+            max_threads: default_threads(),
+            keep_alive: Duration::from_secs(60),
+            queue_capacity: None,
+            queue_policy: QueuePolicy::Block,
+            scheduler: Scheduler::Channel,
+            panic_handler: None,
+            listener: None,
+            config: WorkerConfig::default(),
+        }
+    }
+
+    /// Sets a fixed number of threads in the pool.
+    pub fn num_threads(mut self, n: usize) -> ThreadPoolBuilder {
+        self.min_threads = Some(n);
+        self.max_threads = n;
+        self
+    }
+
+    /// Sets the number of threads the pool keeps even when idle.
+    ///
+    /// Defaults to one per available CPU, or max_threads if that is lower.
+    pub fn min_threads(mut self, n: usize) -> ThreadPoolBuilder {
+        self.min_threads = Some(n);
+        self
+    }
+
+    /// Sets the number of threads the pool may grow to when jobs are queued
+    /// and no worker is idle.
+    pub fn max_threads(mut self, n: usize) -> ThreadPoolBuilder {
+        self.max_threads = n;
+        self
+    }
+
+    /// Sets how long a thread above the minimum may sit idle before it exits.
+    pub fn keep_alive(mut self, keep_alive: Duration) -> ThreadPoolBuilder {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Bounds the job queue to capacity jobs.
+    pub fn queue_capacity(mut self, capacity: usize) -> ThreadPoolBuilder {
+        self.queue_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets what `execute` does when a bounded queue is full.
+    pub fn queue_policy(mut self, policy: QueuePolicy) -> ThreadPoolBuilder {
+        self.queue_policy = policy;
+        self
+    }
+
+    /// Sets how queued jobs are handed to workers.
+    pub fn scheduler(mut self, scheduler: Scheduler) -> ThreadPoolBuilder {
+        self.scheduler = scheduler;
+        self
+    }
+
+    /// Names each worker thread `{prefix}-{id}`.
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> ThreadPoolBuilder {
+        self.config.name_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Sets the stack size, in bytes, of each worker thread.
+    pub fn stack_size(mut self, size: usize) -> ThreadPoolBuilder {
+        self.config.stack_size = Some(size);
+        self
+    }
+
+    /// Sets a closure run on each worker thread before it takes any jobs.
+    ///
+    /// The closure gets the worker's id. It also runs on threads spawned to
+    /// replace workers that died.
+    ///
+    /// If it panics, the thread exits without taking any jobs and is not
+    /// replaced. `build` fails if this happens to one of the first workers;
+    /// later it is reported as `PoolEvent::WorkerSpawnFailed`.
+    pub fn on_start<F>(mut self, f: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_start = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets a closure run on each worker thread as it shuts down.
+    ///
+    /// The closure gets the worker's id.
+    pub fn on_stop<F>(mut self, f: F) -> ThreadPoolBuilder
+    where
+        F: Fn(usize) + Send + Sync + 'static,
+    {
+        self.config.on_stop = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the closure called whenever a job run with `execute` panics.
+    pub fn panic_handler<F>(mut self, f: F) -> ThreadPoolBuilder
+    where
+        F: Fn(&JobPanic<'_>) + Send + Sync + 'static,
+    {
+        self.panic_handler = Some(Arc::new(f));
+        self
+    }
+
+    /// Sets the listener that receives the pool's events. Without one the
+    /// pool logs nothing.
+    pub fn event_listener<L>(mut self, listener: L) -> ThreadPoolBuilder
+    where
+        L: EventListener + 'static,
+    {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+
+    /// Creates the ThreadPool, spawning all of its threads.
+    pub fn build(self) -> Result<ThreadPool, PoolCreationError> {
+        let size = PoolSize {
+            min: self
+                .min_threads
+                .unwrap_or_else(|| default_threads().min(self.max_threads)),
+            max: self.max_threads,
+            keep_alive: self.keep_alive,
+        };
+        if size.max == 0 {
+            return Err(PoolCreationError(String::from("n must be greater than 0")));
+        }
+        if size.min > size.max {
+            return Err(PoolCreationError(String::from(
+                "min threads must not exceed max threads",
+            )));
+        }
+        if self.queue_capacity == Some(0) {
+            return Err(PoolCreationError(String::from(
+                "queue capacity must be greater than 0",
+            )));
+        }
+        ThreadPool::spawn(
+            size,
+            self.queue_capacity,
+            self.queue_policy,
+            self.scheduler,
+            self.config,
+            self.panic_handler,
+            self.listener,
+        )
+        .map_err(|e| PoolCreationError(format!("failed to spawn worker thread: {}", e)))
+    }
+}
+
+impl Default for ThreadPoolBuilder {
+    fn default() -> ThreadPoolBuilder {
+        ThreadPoolBuilder::new()
+    }
+}
+
+impl fmt::Debug for ThreadPoolBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThreadPoolBuilder")
+            .field("min_threads", &self.min_threads)
+            .field("max_threads", &self.max_threads)
+            .field("keep_alive", &self.keep_alive)
+            .field("queue_capacity", &self.queue_capacity)
+            .field("queue_policy", &self.queue_policy)
+            .field("scheduler", &self.scheduler)
+            .field("thread_name", &self.config.name_prefix)
+            .field("stack_size", &self.config.stack_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// One thread per available CPU, or four if that can't be told.
+fn default_threads() -> usize {
+    thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+/// How many workers the pool runs and how long extra ones stay idle.
+pub(crate) struct PoolSize {
+    pub(crate) min: usize,
+    pub(crate) max: usize,
+    pub(crate) keep_alive: Duration,
+}
+
+impl PoolSize {
+    /// True if the pool can grow and shrink.
+    pub(crate) fn is_elastic(&self) -> bool {
+        self.min < self.max
+    }
+}
+
+/// How worker threads are spawned, kept so that replacements match.
+#[derive(Default)]
+pub(crate) struct WorkerConfig {
+    name_prefix: Option<String>,
+    stack_size: Option<usize>,
+    on_start: Option<ThreadHook>,
+    on_stop: Option<ThreadHook>,
+}
+
+impl WorkerConfig {
+    /// Spawns a pool thread. With a name prefix set it is named `{prefix}-{name}`.
+    pub(crate) fn spawn<F>(&self, name: impl fmt::Display, f: F) -> io::Result<JoinHandle<()>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut builder = thread::Builder::new();
+        if let Some(prefix) = &self.name_prefix {
+            builder = builder.name(format!("{}-{}", prefix, name));
+        }
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        builder.spawn(f)
+    }
+
+    /// Runs the on_start hook. Returns the panic message if it panicked.
+    pub(crate) fn started(&self, id: usize) -> Result<(), String> {
+        let Some(on_start) = &self.on_start else {
+            return Ok(());
+        };
+        panic::catch_unwind(AssertUnwindSafe(|| on_start(id))).map_err(|payload| {
+            let message = panic_message(payload.as_ref()).unwrap_or("no message");
+            format!("on_start panicked on worker {}: {}", id, message)
+        })
+    }
+
+    pub(crate) fn stopped(&self, id: usize) {
+        if let Some(on_stop) = &self.on_stop {
+            on_stop(id);
+        }
+    }
+}
+
+type ThreadHook = Arc<dyn Fn(usize) + Send + Sync + 'static>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PoolEvent;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Mutex,
+        },
+        thread,
+    };
+
+    #[test]
+    fn names_threads_and_runs_hooks() {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(Mutex::new(Vec::new()));
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(3)
+            .thread_name("test-worker")
+            .stack_size(256 * 1024)
+            .on_start({
+                let started = Arc::clone(&started);
+                move |id| {
+                    let name = thread::current().name().map(str::to_string);
+                    started.lock().unwrap().push((id, name));
+                }
+            })
+            .on_stop({
+                let stopped = Arc::clone(&stopped);
+                move |id| stopped.lock().unwrap().push(id)
+            })
+            .build()
+            .unwrap();
+
+        let name = pool
+            .submit(|| thread::current().name().map(str::to_string))
+            .join()
+            .unwrap();
+        assert!(name.unwrap().starts_with("test-worker-"));
+        drop(pool);
+
+        let mut started = started.lock().unwrap().clone();
+        started.sort();
+        assert_eq!(
+            started,
+            (0..3)
+                .map(|id| (id, Some(format!("test-worker-{}", id))))
+                .collect::<Vec<_>>()
+        );
+        let mut stopped = stopped.lock().unwrap().clone();
+        stopped.sort();
+        assert_eq!(stopped, [0, 1, 2]);
+    }
+
+    #[test]
+    fn min_threads_follows_a_lower_max() {
+        let pool = ThreadPoolBuilder::new().max_threads(1).build().unwrap();
+        assert_eq!(pool.size(), 1);
+
+        let pool = ThreadPoolBuilder::new()
+            .max_threads(default_threads() + 2)
+            .build()
+            .unwrap();
+        assert_eq!(pool.size(), default_threads());
+
+        let error = ThreadPoolBuilder::new()
+            .min_threads(3)
+            .max_threads(2)
+            .build()
+            .err()
+            .unwrap();
+        assert!(error.to_string().contains("must not exceed"), "{}", error);
+        assert!(ThreadPoolBuilder::new().max_threads(0).build().is_err());
+        assert!(ThreadPoolBuilder::new().queue_capacity(0).build().is_err());
+    }
+
+    #[test]
+    fn build_fails_if_on_start_panics() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let result = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .on_start({
+                let calls = Arc::clone(&calls);
+                move |_| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    panic!("no database");
+                }
+            })
+            .build();
+        let error = result.err().expect("build should fail").to_string();
+        assert!(error.contains("no database"), "{}", error);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn worker_whose_on_start_panics_is_not_replaced() {
+        let (tx, rx) = mpsc::channel();
+        let tx = Mutex::new(tx);
+        let calls = Arc::new(AtomicUsize::new(0));
+        let pool = ThreadPoolBuilder::new()
+            .min_threads(1)
+            .max_threads(2)
+            .on_start({
+                let calls = Arc::clone(&calls);
+                move |id| {
+                    calls.fetch_add(1, Ordering::SeqCst);
+                    assert_eq!(id, 0, "only the first worker starts");
+                }
+            })
+            .event_listener(move |event: &PoolEvent<'_>| {
+                if let PoolEvent::WorkerSpawnFailed { error } = event {
+                    tx.lock().unwrap().send(error.to_string()).unwrap();
+                }
+            })
+            .build()
+            .unwrap();
+
+        // Two jobs at once make the pool grow, and the new worker panics.
+        let (unblock, blocked) = mpsc::channel::<()>();
+        pool.execute(move || {
+            let _ = blocked.recv();
+        });
+        pool.execute(|| {});
+        let error = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(error.contains("only the first worker starts"), "{}", error);
+        thread::sleep(Duration::from_millis(50));
+        drop(unblock);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert_eq!(pool.size(), 1);
+    }
+}
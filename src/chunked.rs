@@ -0,0 +1,165 @@
+use std::io::{self, BufRead, Write};
+
+use crate::{
+    request::{read_fields, read_line, Line},
+    Headers, Limits, ParseError,
+};
+
+/// Writes a body with chunked transfer coding.
+///
+/// Each `write` becomes one chunk. The body must be ended with `finish` or
+/// `finish_with_trailers`, which write the last chunk; dropping the writer
+/// leaves the body unterminated.
+///
+/// Response does this for a body of unknown length, so a ChunkedWriter is
+/// only needed when writing a response head by hand.
+pub struct ChunkedWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> ChunkedWriter<W> {
+    pub fn new(inner: W) -> ChunkedWriter<W> {
+        ChunkedWriter { inner }
+    }
+
+    /// Writes the zero-length last chunk and an empty trailer section, and
+    /// returns the inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        self.finish_with_trailers(&Headers::new())
+    }
+
+    /// Writes the last chunk followed by trailer fields.
+    pub fn finish_with_trailers(mut self, trailers: &Headers) -> io::Result<W> {
+        write!(self.inner, "0\r\n{}\r\n", trailers)?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for ChunkedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // A zero-length chunk would end the body early.
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        write!(self.inner, "{:X}\r\n", buf.len())?;
+        self.inner.write_all(buf)?;
+        self.inner.write_all(b"\r\n")?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Reads a chunked body (RFC 9112 7.1), returning the decoded body and its
+/// trailer fields.
+///
+/// The decoded body is held to `limits.max_body`, and the trailers to the
+/// same limits as the header section. Chunk extensions are ignored.
+pub(crate) fn read_chunked<R: BufRead>(
+    reader: &mut R,
+    limits: &Limits,
+) -> Result<(Vec<u8>, Headers), ParseError> {
+    let mut body = Vec::new();
+    loop {
+        let line = match read_line(reader, limits.max_request_line)? {
+            Line::Complete(line) => line,
+            Line::TooLong => return Err(ParseError::BadRequest("chunk size line too long")),
+            Line::Eof => return Err(ParseError::BadRequest("unexpected end of chunked body")),
+        };
+        let size = line
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim_end_matches([' ', '\t']);
+        if size.is_empty() || !size.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(ParseError::BadRequest("invalid chunk size"));
+        }
+        // A size too big for a u64 is certainly too big for the limit.
+        let size = u64::from_str_radix(size, 16).map_err(|_| ParseError::BodyTooLarge)?;
+        if size == 0 {
+            break;
+        }
+        let start = body.len();
+        let end = usize::try_from(size)
+            .ok()
+            .and_then(|size| start.checked_add(size))
+            .filter(|&end| end <= limits.max_body)
+            .ok_or(ParseError::BodyTooLarge)?;
+        body.resize(end, 0);
+        reader
+            .read_exact(&mut body[start..])
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => {
+                    ParseError::BadRequest("chunk shorter than its size")
+                }
+                _ => ParseError::Io(e),
+            })?;
+        match read_line(reader, 0)? {
+            Line::Complete(_) => {}
+            _ => return Err(ParseError::BadRequest("missing CRLF after chunk data")),
+        }
+    }
+
+    let trailers = read_fields(reader, limits)?;
+    Ok((body, trailers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &str) -> Result<(Vec<u8>, Headers), ParseError> {
+        read_chunked(&mut input.as_bytes(), &Limits::default())
+    }
+
+    #[test]
+    fn decodes_chunks_and_trailers() {
+        let (body, trailers) = read("3;ext=1\r\nabc\r\n2\r\nde\r\n0\r\nX-Sum: 5\r\n\r\n").unwrap();
+        assert_eq!(body, b"abcde");
+        assert_eq!(trailers.get("X-Sum"), Some("5"));
+    }
+
+    #[test]
+    fn huge_chunk_after_data_is_too_large() {
+        let result = read("1\r\na\r\nFFFFFFFFFFFFFFFF\r\n");
+        assert!(matches!(result, Err(ParseError::BodyTooLarge)));
+    }
+
+    #[test]
+    fn enforces_max_body() {
+        let limits = Limits {
+            max_body: 4,
+            ..Limits::default()
+        };
+        let result = read_chunked(&mut &b"3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n"[..], &limits);
+        assert!(matches!(result, Err(ParseError::BodyTooLarge)));
+    }
+
+    #[test]
+    fn rejects_malformed_chunks() {
+        for input in [
+            "x\r\n",
+            "3\r\nabcd\r\n0\r\n\r\n",
+            "5\r\nabc",
+            "3\r\nabc\r\n",
+        ] {
+            assert!(
+                matches!(read(input), Err(ParseError::BadRequest(_))),
+                "{:?}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn writer_frames_each_write() {
+        let mut writer = ChunkedWriter::new(Vec::new());
+        writer.write_all(b"hello").unwrap();
+        writer.write_all(b"").unwrap();
+        let out = writer.finish().unwrap();
+        assert_eq!(out, b"5\r\nhello\r\n0\r\n\r\n");
+    }
+}
@@ -0,0 +1,284 @@
+use crate::{
+    deflate::{self, EncodeReader, EncodeWriter, Format},
+    Body, Headers, Middleware, Next, Request, Response, StatusCode,
+};
+
+/// Media types compressed by default. Types ending in `+json` or `+xml`
+/// are compressed too.
+const COMPRESSIBLE: &[&str] = &[
+    "text/",
+    "application/json",
+    "application/javascript",
+    "application/xml",
+    "application/wasm",
+    "application/manifest+json",
+    "image/svg+xml",
+];
+
+/// Middleware that compresses responses with gzip or deflate when the
+/// client's `Accept-Encoding` allows it.
+///
+/// Only responses with a compressible Content-Type and a body of at least
+/// `min_size` bytes are compressed. Responses that already have a
+/// `Content-Encoding`, partial content and `Cache-Control: no-transform`
+/// responses are left alone. Compressible responses get
+/// `Vary: Accept-Encoding` whether or not they were compressed.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    min_size: u64,
+    types: Vec<String>,
+}
+
+impl Compression {
+    pub fn new() -> Compression {
+        Compression {
+            min_size: 1024,
+            types: COMPRESSIBLE.iter().map(|t| t.to_string()).collect(),
+        }
+    }
+
+    /// Sets the smallest body to compress, in bytes. Defaults to 1024;
+    /// smaller bodies gain little. Bodies of unknown length are always
+    /// compressed.
+    pub fn min_size(mut self, bytes: u64) -> Compression {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Adds a media type to compress. A type ending in `/`, such as
+    /// `text/`, covers every subtype.
+    pub fn compress_type(mut self, media_type: impl Into<String>) -> Compression {
+        self.types.push(media_type.into().to_ascii_lowercase());
+        self
+    }
+
+    fn is_compressible(&self, content_type: &str) -> bool {
+        let media_type = content_type
+            .split(';')
+            .next()
+            .unwrap_or("")
+            .trim()
+            .to_ascii_lowercase();
+        media_type.ends_with("+json")
+            || media_type.ends_with("+xml")
+            || self.types.iter().any(|t| {
+                if t.ends_with('/') {
+                    media_type.starts_with(t.as_str())
+                } else {
+                    media_type == *t
+                }
+            })
+    }
+}
+
+impl Default for Compression {
+    fn default() -> Compression {
+        Compression::new()
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        let mut response = next.run(request);
+        let status = response.status();
+        if status == StatusCode::NotModified {
+            restore_coded_etag(request, &mut response);
+            return response;
+        }
+        let headers = response.headers();
+        if status.forbids_body()
+            || status == StatusCode::PartialContent
+            || headers.contains("Content-Encoding")
+            || headers.has_value("Cache-Control", "no-transform")
+            || !headers
+                .get("Content-Type")
+                .is_some_and(|t| self.is_compressible(t))
+        {
+            return response;
+        }
+        add_vary(response.headers_mut());
+        if response.body().len().is_some_and(|len| len < self.min_size) {
+            return response;
+        }
+        let (coding, format) = match negotiate(request.headers(), &["gzip", "deflate"]) {
+            Some("gzip") => ("gzip", Format::Gzip),
+            Some("deflate") => ("deflate", Format::Zlib),
+            _ => return response,
+        };
+
+        let body = match response.take_body() {
+            Body::Empty => Body::Empty,
+            Body::Bytes(bytes) => Body::Bytes(deflate::compress(format, &bytes)),
+            Body::Stream { reader, .. } => Body::Stream {
+                reader: Box::new(EncodeReader::new(reader, format)),
+                length: None,
+            },
+            Body::Writer(write) => Body::Writer(Box::new(move |out| {
+                let mut encoder = EncodeWriter::new(out, format);
+                write(&mut encoder)?;
+                encoder.finish()?;
+                Ok(())
+            })),
+        };
+        response.set_body(body);
+        let headers = response.headers_mut();
+        headers.insert("Content-Encoding", coding);
+        // Ranges of the identity bytes don't apply to the compressed ones.
+        headers.remove("Accept-Ranges");
+        // The compressed bytes are a different representation, so they
+        // need their own entity tag.
+        if let Some(etag) = headers
+            .get("ETag")
+            .and_then(|etag| coded_etag(etag, coding))
+        {
+            headers.insert("ETag", etag);
+        }
+        response
+    }
+}
+
+/// Returns etag with the coding added, e.g. `"abc"` to `"abc-gzip"`.
+fn coded_etag(etag: &str, coding: &str) -> Option<String> {
+    let opaque = etag.strip_suffix('"')?;
+    Some(format!("{}-{}\"", opaque, coding))
+}
+
+/// Gives a 304 the entity tag of the compressed representation if that is
+/// the one the client's `If-None-Match` matched.
+fn restore_coded_etag(request: &Request, response: &mut Response) {
+    let etag = match response.headers().get("ETag") {
+        Some(etag) => etag,
+        None => return,
+    };
+    let if_none_match: Vec<&str> = request.headers().get_all("If-None-Match").collect();
+    let coded = ["gzip", "deflate"]
+        .iter()
+        .filter_map(|coding| coded_etag(etag, coding))
+        .find(|coded| if_none_match.iter().any(|v| v.contains(coded.as_str())));
+    if let Some(coded) = coded {
+        let headers = response.headers_mut();
+        headers.insert("ETag", coded);
+        add_vary(headers);
+    }
+}
+
+/// Adds `Accept-Encoding` to the response's `Vary` header.
+pub(crate) fn add_vary(headers: &mut Headers) {
+    if headers.has_value("Vary", "Accept-Encoding") || headers.has_value("Vary", "*") {
+        return;
+    }
+    match headers.get("Vary") {
+        Some(vary) => {
+            let vary = format!("{}, Accept-Encoding", vary);
+            headers.insert("Vary", vary);
+        }
+        None => headers.insert("Vary", "Accept-Encoding"),
+    }
+}
+
+/// Picks the content coding the client prefers among offered, by the
+/// q-values in its `Accept-Encoding` (RFC 9110 12.5.3). Ties go to the
+/// earlier offer. Returns None if there is no header or nothing offered is
+/// acceptable.
+pub(crate) fn negotiate<'a>(headers: &Headers, offered: &[&'a str]) -> Option<&'a str> {
+    if !headers.contains("Accept-Encoding") {
+        return None;
+    }
+    let mut wildcard = None;
+    let mut explicit: Vec<(String, f32)> = Vec::new();
+    for element in headers.values("Accept-Encoding") {
+        let mut parts = element.split(';');
+        let coding = parts.next().unwrap_or("").trim().to_ascii_lowercase();
+        let mut q = 1.0;
+        for param in parts {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("q") {
+                    q = match value.trim().parse::<f32>() {
+                        Ok(q) if (0.0..=1.0).contains(&q) => q,
+                        _ => 0.0,
+                    };
+                }
+            }
+        }
+        let coding = match coding.as_str() {
+            "x-gzip" => "gzip".to_string(),
+            _ => coding,
+        };
+        if coding == "*" {
+            wildcard = Some(q);
+        } else {
+            explicit.push((coding, q));
+        }
+    }
+
+    let mut best: Option<(&'a str, f32)> = None;
+    for &coding in offered {
+        let q = explicit
+            .iter()
+            .find(|(c, _)| c == coding)
+            .map(|&(_, q)| q)
+            .or(wildcard)
+            .unwrap_or(0.0);
+        if q > 0.0 && best.is_none_or(|(_, best_q)| q > best_q) {
+            best = Some((coding, q));
+        }
+    }
+    best.map(|(coding, _)| coding)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OFFERED: &[&str] = &["gzip", "deflate"];
+
+    fn pick(accept_encoding: &[&str]) -> Option<&'static str> {
+        let mut headers = Headers::new();
+        for value in accept_encoding {
+            headers.append("Accept-Encoding", *value);
+        }
+        negotiate(&headers, OFFERED)
+    }
+
+    #[test]
+    fn without_header_nothing_is_picked() {
+        assert_eq!(pick(&[]), None);
+        assert_eq!(pick(&[""]), None);
+        assert_eq!(pick(&["identity"]), None);
+    }
+
+    #[test]
+    fn picks_the_highest_q_value() {
+        assert_eq!(pick(&["gzip, deflate"]), Some("gzip"));
+        assert_eq!(pick(&["gzip;q=0.5, deflate"]), Some("deflate"));
+        assert_eq!(pick(&["deflate;q=0.8", "GZIP;Q=0.9"]), Some("gzip"));
+        assert_eq!(pick(&["x-gzip"]), Some("gzip"));
+    }
+
+    #[test]
+    fn ties_go_to_the_earlier_offer() {
+        assert_eq!(pick(&["deflate;q=0.5, gzip;q=0.5"]), Some("gzip"));
+        assert_eq!(pick(&["*"]), Some("gzip"));
+    }
+
+    #[test]
+    fn zero_q_value_refuses_a_coding() {
+        assert_eq!(pick(&["gzip;q=0"]), None);
+        assert_eq!(pick(&["gzip;q=0, deflate"]), Some("deflate"));
+        assert_eq!(pick(&["gzip;q=0, *"]), Some("deflate"));
+        assert_eq!(pick(&["*;q=0"]), None);
+    }
+
+    #[test]
+    fn wildcard_covers_codings_not_listed() {
+        assert_eq!(pick(&["*;q=0.5"]), Some("gzip"));
+        assert_eq!(pick(&["gzip;q=0.4, *;q=0.5"]), Some("deflate"));
+        assert_eq!(pick(&["br, *;q=0.1"]), Some("gzip"));
+    }
+
+    #[test]
+    fn invalid_q_values_count_as_zero() {
+        assert_eq!(pick(&["gzip;q=2"]), None);
+        assert_eq!(pick(&["gzip;q=x, deflate;q=0.1"]), Some("deflate"));
+    }
+}
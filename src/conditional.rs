@@ -0,0 +1,360 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::{httpdate, Method, Request, StatusCode};
+
+/// The validators of a representation: its entity tag and modification
+/// time.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Validators {
+    /// The entity tag, quotes and `W/` prefix included.
+    pub(crate) etag: Option<String>,
+    pub(crate) last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    /// Makes validators for a file from its length and modification time.
+    pub(crate) fn for_file(len: u64, modified: Option<SystemTime>, weak: bool) -> Validators {
+        let etag = modified.map(|modified| {
+            let since_epoch = modified.duration_since(UNIX_EPOCH).unwrap_or_default();
+            format!(
+                "{}\"{:x}-{:x}-{:x}\"",
+                if weak { "W/" } else { "" },
+                since_epoch.as_secs(),
+                since_epoch.subsec_nanos(),
+                len
+            )
+        });
+        Validators {
+            etag,
+            last_modified: modified,
+        }
+    }
+
+    /// Evaluates the request's preconditions in the order of RFC 9110 13.2.2.
+    ///
+    /// Returns 304 or 412 if the request should get that instead of the
+    /// representation, or None if it should be served as normal.
+    pub(crate) fn check(&self, request: &Request) -> Option<StatusCode> {
+        let headers = request.headers();
+        if headers.contains("If-Match") {
+            if !self.matches(request, "If-Match", true) {
+                return Some(StatusCode::PreconditionFailed);
+            }
+        } else if let Some(since) = headers.get("If-Unmodified-Since").and_then(httpdate::parse) {
+            if self.last_modified.is_some() && self.modified_after(since) {
+                return Some(StatusCode::PreconditionFailed);
+            }
+        }
+
+        let safe = matches!(request.method(), Method::Get | Method::Head);
+        if headers.contains("If-None-Match") {
+            if self.matches(request, "If-None-Match", false) {
+                return Some(if safe {
+                    StatusCode::NotModified
+                } else {
+                    StatusCode::PreconditionFailed
+                });
+            }
+        } else if let Some(since) = headers.get("If-Modified-Since").and_then(httpdate::parse) {
+            if safe && !self.modified_after(since) {
+                return Some(StatusCode::NotModified);
+            }
+        }
+        None
+    }
+
+    /// Returns true if a `Range` header should be honoured: the request has
+    /// no `If-Range`, or its validator still matches (RFC 9110 13.1.5).
+    pub(crate) fn if_range(&self, request: &Request) -> bool {
+        let value = match request.header("If-Range") {
+            Some(value) => value.trim(),
+            None => return true,
+        };
+        if value.starts_with('"') || value.starts_with("W/") {
+            // If-Range always uses the strong comparison.
+            return match &self.etag {
+                Some(etag) => !etag.starts_with("W/") && value == etag,
+                None => false,
+            };
+        }
+        match (httpdate::parse(value), self.last_modified) {
+            (Some(date), Some(modified)) => unix_secs(date) == unix_secs(modified),
+            _ => false,
+        }
+    }
+
+    /// Returns true if any entity tag in the named header matches ours.
+    ///
+    /// Strong comparison needs both tags to be strong; weak comparison
+    /// ignores the `W/` prefix, and the coding suffix Compression adds to
+    /// the tags of compressed responses.
+    fn matches(&self, request: &Request, header: &str, strong: bool) -> bool {
+        let etag = match &self.etag {
+            Some(etag) => etag,
+            None => return false,
+        };
+        request
+            .headers()
+            .get_all(header)
+            .flat_map(entity_tags)
+            .any(|tag| {
+                if tag == "*" {
+                    return true;
+                }
+                if strong {
+                    !tag.starts_with("W/") && !etag.starts_with("W/") && tag == etag
+                } else {
+                    let tag = tag.trim_start_matches("W/");
+                    let tag = ["-gzip\"", "-deflate\""]
+                        .iter()
+                        .find_map(|suffix| tag.strip_suffix(suffix))
+                        .map_or(tag.to_string(), |opaque| format!("{}\"", opaque));
+                    tag == etag.trim_start_matches("W/")
+                }
+            })
+    }
+
+    /// Returns true if the representation changed after since, or has no
+    /// modification time. Times are compared to the second, since HTTP dates
+    /// have no finer precision.
+    fn modified_after(&self, since: SystemTime) -> bool {
+        match self.last_modified {
+            Some(modified) => unix_secs(modified) > unix_secs(since),
+            None => true,
+        }
+    }
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Splits a header value into entity tags and `*`.
+///
+/// Tags are split at the commas between them rather than at every comma,
+/// since a comma may appear inside the quotes.
+fn entity_tags(value: &str) -> Vec<&str> {
+    let mut tags = Vec::new();
+    let mut rest = value.trim_start_matches([' ', '\t', ',']);
+    while !rest.is_empty() {
+        let opaque_start = if rest.starts_with("W/\"") { 2 } else { 0 };
+        let end = if rest[opaque_start..].starts_with('"') {
+            match rest[opaque_start + 1..].find('"') {
+                Some(i) => opaque_start + i + 2,
+                None => rest.len(),
+            }
+        } else {
+            rest.find(',').unwrap_or(rest.len())
+        };
+        tags.push(rest[..end].trim());
+        rest = rest[end..].trim_start_matches([' ', '\t', ',']);
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn modified() -> SystemTime {
+        UNIX_EPOCH + Duration::from_millis(1_700_000_000_500)
+    }
+
+    fn validators(etag: &str) -> Validators {
+        Validators {
+            etag: Some(etag.to_string()),
+            last_modified: Some(modified()),
+        }
+    }
+
+    fn request(method: &str, headers: &[&str]) -> Request {
+        let raw = format!(
+            "{} / HTTP/1.1\r\nHost: a\r\n{}\r\n",
+            method,
+            headers
+                .iter()
+                .map(|h| format!("{}\r\n", h))
+                .collect::<String>()
+        );
+        Request::read_from(&mut raw.as_bytes()).unwrap()
+    }
+
+    fn check(validators: &Validators, method: &str, header: &str) -> Option<StatusCode> {
+        validators.check(&request(method, &[header]))
+    }
+
+    #[test]
+    fn matching_if_none_match_is_304_for_safe_methods_and_412_otherwise() {
+        let v = validators("\"abc\"");
+        let header = "If-None-Match: \"abc\"";
+        assert_eq!(check(&v, "GET", header), Some(StatusCode::NotModified));
+        assert_eq!(check(&v, "HEAD", header), Some(StatusCode::NotModified));
+        assert_eq!(
+            check(&v, "PUT", header),
+            Some(StatusCode::PreconditionFailed)
+        );
+        assert_eq!(
+            check(&v, "DELETE", header),
+            Some(StatusCode::PreconditionFailed)
+        );
+        assert_eq!(check(&v, "GET", "If-None-Match: \"xyz\""), None);
+        assert_eq!(v.check(&request("GET", &[])), None);
+    }
+
+    #[test]
+    fn if_match_uses_the_strong_comparison() {
+        let strong = validators("\"abc\"");
+        let weak = validators("W/\"abc\"");
+        assert_eq!(check(&strong, "PUT", "If-Match: \"abc\""), None);
+        assert_eq!(
+            check(&strong, "PUT", "If-Match: W/\"abc\""),
+            Some(StatusCode::PreconditionFailed)
+        );
+        assert_eq!(
+            check(&weak, "PUT", "If-Match: W/\"abc\""),
+            Some(StatusCode::PreconditionFailed)
+        );
+        assert_eq!(
+            check(&strong, "PUT", "If-Match: \"xyz\""),
+            Some(StatusCode::PreconditionFailed)
+        );
+    }
+
+    #[test]
+    fn if_none_match_uses_the_weak_comparison() {
+        let strong = validators("\"abc\"");
+        let weak = validators("W/\"abc\"");
+        for (v, header) in [
+            (&strong, "If-None-Match: W/\"abc\""),
+            (&weak, "If-None-Match: \"abc\""),
+            (&weak, "If-None-Match: W/\"abc\""),
+        ] {
+            assert_eq!(
+                check(v, "GET", header),
+                Some(StatusCode::NotModified),
+                "{:?} with {}",
+                v.etag,
+                header
+            );
+        }
+    }
+
+    #[test]
+    fn star_matches_any_current_representation() {
+        let v = validators("\"abc\"");
+        let untagged = Validators {
+            etag: None,
+            last_modified: Some(modified()),
+        };
+        assert_eq!(check(&v, "PUT", "If-Match: *"), None);
+        assert_eq!(
+            check(&v, "GET", "If-None-Match: *"),
+            Some(StatusCode::NotModified)
+        );
+        assert_eq!(
+            check(&untagged, "PUT", "If-Match: *"),
+            Some(StatusCode::PreconditionFailed)
+        );
+    }
+
+    #[test]
+    fn weak_comparison_ignores_the_coding_suffix() {
+        let v = validators("\"abc\"");
+        assert_eq!(
+            check(&v, "GET", "If-None-Match: W/\"abc-gzip\""),
+            Some(StatusCode::NotModified)
+        );
+        assert_eq!(
+            check(&v, "GET", "If-None-Match: \"abc-deflate\""),
+            Some(StatusCode::NotModified)
+        );
+        assert_eq!(check(&v, "GET", "If-None-Match: \"abc-br\""), None);
+        assert_eq!(
+            check(&v, "PUT", "If-Match: \"abc-gzip\""),
+            Some(StatusCode::PreconditionFailed)
+        );
+    }
+
+    #[test]
+    fn splits_tags_only_outside_quotes() {
+        assert_eq!(
+            entity_tags(" \"a,b\", W/\"c\" ,,*"),
+            ["\"a,b\"", "W/\"c\"", "*"]
+        );
+        assert_eq!(entity_tags("\"open"), ["\"open"]);
+        assert!(entity_tags(" , ").is_empty());
+
+        let v = validators("\"a,b\"");
+        assert_eq!(
+            check(&v, "GET", "If-None-Match: \"x\", \"a,b\""),
+            Some(StatusCode::NotModified)
+        );
+        assert_eq!(check(&v, "GET", "If-None-Match: \"a\", \"b\""), None);
+    }
+
+    #[test]
+    fn dates_are_compared_to_the_second() {
+        let v = validators("\"abc\"");
+        let same = httpdate::format(modified());
+        let earlier = httpdate::format(modified() - Duration::from_secs(60));
+        let if_modified = |date: &str| format!("If-Modified-Since: {}", date);
+        let if_unmodified = |date: &str| format!("If-Unmodified-Since: {}", date);
+
+        assert_eq!(
+            check(&v, "GET", &if_modified(&same)),
+            Some(StatusCode::NotModified)
+        );
+        assert_eq!(check(&v, "GET", &if_modified(&earlier)), None);
+        assert_eq!(check(&v, "POST", &if_modified(&same)), None);
+        assert_eq!(check(&v, "PUT", &if_unmodified(&same)), None);
+        assert_eq!(
+            check(&v, "PUT", &if_unmodified(&earlier)),
+            Some(StatusCode::PreconditionFailed)
+        );
+    }
+
+    #[test]
+    fn entity_tags_take_precedence_over_dates() {
+        let v = validators("\"abc\"");
+        let same = httpdate::format(modified());
+        let earlier = httpdate::format(modified() - Duration::from_secs(60));
+        let request = |headers: &[&str]| v.check(&request("GET", headers));
+
+        assert_eq!(
+            request(&[
+                "If-None-Match: \"xyz\"",
+                &format!("If-Modified-Since: {}", same)
+            ]),
+            None
+        );
+        assert_eq!(
+            request(&[
+                "If-Match: \"abc\"",
+                &format!("If-Unmodified-Since: {}", earlier)
+            ]),
+            None
+        );
+    }
+
+    #[test]
+    fn if_range_needs_a_strong_match_or_the_exact_date() {
+        let strong = validators("\"abc\"");
+        let weak = validators("W/\"abc\"");
+        let if_range = |v: &Validators, value: &str| {
+            v.if_range(&request("GET", &[&format!("If-Range: {}", value)]))
+        };
+
+        assert!(strong.if_range(&request("GET", &[])));
+        assert!(if_range(&strong, "\"abc\""));
+        assert!(!if_range(&strong, "\"xyz\""));
+        assert!(!if_range(&strong, "W/\"abc\""));
+        assert!(!if_range(&weak, "W/\"abc\""));
+        assert!(if_range(&strong, &httpdate::format(modified())));
+        assert!(!if_range(
+            &strong,
+            &httpdate::format(modified() - Duration::from_secs(1))
+        ));
+        assert!(!if_range(&strong, "not a date"));
+    }
+}
@@ -0,0 +1,990 @@
+use std::{
+    error::Error,
+    fmt, fs, io,
+    net::ToSocketAddrs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{router::check_pattern, KeepAlive, Limits, Method, MimeTypes, StatusCode};
+
+/// Server settings read from a config file.
+///
+/// The file is a small subset of TOML: `[table]` and `[[array]]` headers,
+/// `key = value` lines with string, integer and boolean values, and `#`
+/// comments. For example:
+///
+/// ```text
+/// [server]
+/// workers = 8
+/// access_log = true
+///
+/// [[listener]]
+/// bind = "0.0.0.0:8080"
+///
+/// [[root]]
+/// prefix = "/static"
+/// dir = "public"
+///
+/// [[route]]
+/// path = "/"
+/// file = "hello.html"
+///
+/// [[route]]
+/// path = "/old"
+/// status = 301
+/// location = "/"
+///
+/// [error_pages]
+/// 404 = "404.html"
+///
+/// [mime_types]
+/// md = "text/markdown"
+///
+/// [limits]
+/// max_body = 65536
+///
+/// [keep_alive]
+/// idle_timeout = 10
+/// ```
+///
+/// Relative paths are resolved against the directory the config file is
+/// in. Files and directories must exist when the config is read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Worker threads kept running. Set with `workers` in `[server]`.
+    pub workers: usize,
+    /// Whether responses are compressed. Set with `compression` in
+    /// `[server]`.
+    pub compression: bool,
+    /// Whether each request is logged to stderr. Set with `access_log` in
+    /// `[server]`.
+    pub access_log: bool,
+    /// Addresses to listen on, one per `[[listener]]` table. Defaults to
+    /// `127.0.0.1:7878` if there are none.
+    pub listeners: Vec<String>,
+    /// Directories served as static files, one per `[[root]]` table.
+    pub roots: Vec<DocumentRoot>,
+    /// Routes, one per `[[route]]` table, in the order they are tried.
+    pub routes: Vec<RouteConfig>,
+    /// Files sent as the body of responses with an error status, from the
+    /// `[error_pages]` table.
+    pub error_pages: Vec<(StatusCode, PathBuf)>,
+    /// The built-in table with the extensions in the `[mime_types]` table
+    /// added or replaced.
+    pub mime_types: MimeTypes,
+    /// From the `[limits]` table.
+    pub limits: Limits,
+    /// From the `[keep_alive]` table. `idle_timeout` is in seconds.
+    pub keep_alive: KeepAlive,
+}
+
+/// A directory served under a URL prefix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocumentRoot {
+    /// The URL prefix, such as `/static`. Defaults to `/`, which serves
+    /// every path no route matches.
+    pub prefix: String,
+    pub dir: PathBuf,
+    /// Defaults to true. See `StaticFiles::precompressed`.
+    pub precompressed: bool,
+    /// Defaults to false. See `StaticFiles::weak_etags`.
+    pub weak_etags: bool,
+}
+
+/// A route from a method and path pattern to a file or a status code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteConfig {
+    /// Defaults to GET.
+    pub method: Method,
+    /// A Router pattern.
+    pub path: String,
+    pub action: RouteAction,
+}
+
+/// What a route answers with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteAction {
+    /// Serve a file with 200 OK.
+    File(PathBuf),
+    /// Answer with a status code and, for redirects, a `Location`.
+    Status {
+        status: StatusCode,
+        location: Option<String>,
+    },
+}
+
+impl Config {
+    /// Reads and validates the config file at path.
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        let base = path.parent().unwrap_or(Path::new(""));
+        Config::parse_in(&text, base)
+    }
+
+    /// Parses and validates a config. Relative paths are left relative to
+    /// the current directory.
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        Config::parse_in(text, Path::new(""))
+    }
+
+    fn parse_in(text: &str, base: &Path) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        let mut listeners = Vec::new();
+        for table in parse_document(text)? {
+            let mut fields = Fields::new(table.entries);
+            match (table.name.as_str(), table.array) {
+                ("", _) => {}
+                ("server", false) => {
+                    if let Some(entry) = fields.take("workers") {
+                        config.workers = entry.positive()?;
+                    }
+                    if let Some(entry) = fields.take("compression") {
+                        config.compression = entry.boolean()?;
+                    }
+                    if let Some(entry) = fields.take("access_log") {
+                        config.access_log = entry.boolean()?;
+                    }
+                }
+                ("limits", false) => {
+                    let limits = &mut config.limits;
+                    for (key, slot) in [
+                        ("max_request_line", &mut limits.max_request_line),
+                        ("max_header_bytes", &mut limits.max_header_bytes),
+                        ("max_headers", &mut limits.max_headers),
+                        ("max_body", &mut limits.max_body),
+                    ] {
+                        if let Some(entry) = fields.take(key) {
+                            *slot = entry.positive()?;
+                        }
+                    }
+                }
+                ("keep_alive", false) => {
+                    if let Some(entry) = fields.take("idle_timeout") {
+                        let secs = entry.integer(0)?;
+                        config.keep_alive.idle_timeout = Duration::from_secs(secs as u64);
+                    }
+                    if let Some(entry) = fields.take("max_requests") {
+                        config.keep_alive.max_requests = entry.positive()?;
+                    }
+                }
+                ("listener", true) => {
+                    let entry = fields.require("bind", table.pos)?;
+                    listeners.push(entry.address()?);
+                }
+                ("root", true) => {
+                    let root = parse_root(&mut fields, table.pos, base)?;
+                    if config.roots.iter().any(|r| r.prefix == root.prefix) {
+                        return Err(table.pos.error(format!(
+                            "more than one [[root]] has prefix {:?}",
+                            root.prefix
+                        )));
+                    }
+                    config.roots.push(root);
+                }
+                ("route", true) => {
+                    let route = parse_route(&mut fields, table.pos, base)?;
+                    config.routes.push(route);
+                }
+                ("error_pages", false) => {
+                    for entry in fields.take_all() {
+                        let status = entry
+                            .key
+                            .parse()
+                            .ok()
+                            .and_then(StatusCode::from_u16)
+                            .filter(|s| s.is_client_error() || s.is_server_error())
+                            .ok_or_else(|| {
+                                entry.key_pos.error(format!(
+                                    "'{}' is not a 4xx or 5xx status code",
+                                    entry.key
+                                ))
+                            })?;
+                        config.error_pages.push((status, entry.file(base)?));
+                    }
+                }
+                ("mime_types", false) => {
+                    for entry in fields.take_all() {
+                        let mime = entry.string()?;
+                        if !mime.contains('/') {
+                            return Err(entry
+                                .value_pos
+                                .error(format!("{:?} is not a media type", mime)));
+                        }
+                        config.mime_types.insert(&entry.key, mime);
+                    }
+                }
+                (name @ ("listener" | "root" | "route"), false) => {
+                    return Err(table.pos.error(format!(
+                        "'{}' is an array of tables; use [[{}]]",
+                        name, name
+                    )));
+                }
+                (
+                    name @ ("server" | "limits" | "keep_alive" | "error_pages" | "mime_types"),
+                    true,
+                ) => {
+                    return Err(table
+                        .pos
+                        .error(format!("'{}' is a table; use [{}]", name, name)));
+                }
+                (name, _) => {
+                    return Err(table.pos.error(format!("unknown table '{}'", name)));
+                }
+            }
+            fields.finish(&table.name)?;
+        }
+        if !listeners.is_empty() {
+            config.listeners = listeners;
+        }
+        Ok(config)
+    }
+}
+
+impl Default for Config {
+    /// One listener on `127.0.0.1:7878` and four workers, with the
+    /// built-in media types, no access log and no roots, routes or error
+    /// pages.
+    fn default() -> Config {
+        Config {
+            workers: 4,
+            compression: true,
+            access_log: false,
+            listeners: vec!["127.0.0.1:7878".to_string()],
+            roots: Vec::new(),
+            routes: Vec::new(),
+            error_pages: Vec::new(),
+            mime_types: MimeTypes::new(),
+            limits: Limits::default(),
+            keep_alive: KeepAlive::default(),
+        }
+    }
+}
+
+fn parse_root(fields: &mut Fields, pos: Pos, base: &Path) -> Result<DocumentRoot, ConfigError> {
+    let dir = fields.require("dir", pos)?.dir(base)?;
+    let prefix = match fields.take("prefix") {
+        Some(entry) => {
+            let prefix = entry.string()?;
+            if !prefix.starts_with('/') || prefix.split('/').any(|s| s.starts_with([':', '*'])) {
+                return Err(entry.value_pos.error(format!(
+                    "prefix {:?} must start with '/' and have no parameters",
+                    prefix
+                )));
+            }
+            match prefix.trim_end_matches('/') {
+                "" => "/".to_string(),
+                trimmed => trimmed.to_string(),
+            }
+        }
+        None => "/".to_string(),
+    };
+    let precompressed = match fields.take("precompressed") {
+        Some(entry) => entry.boolean()?,
+        None => true,
+    };
+    let weak_etags = match fields.take("weak_etags") {
+        Some(entry) => entry.boolean()?,
+        None => false,
+    };
+    Ok(DocumentRoot {
+        prefix,
+        dir,
+        precompressed,
+        weak_etags,
+    })
+}
+
+fn parse_route(fields: &mut Fields, pos: Pos, base: &Path) -> Result<RouteConfig, ConfigError> {
+    let entry = fields.require("path", pos)?;
+    let path = entry.string()?;
+    check_pattern(&path).map_err(|e| entry.value_pos.error(e))?;
+    let method = match fields.take("method") {
+        Some(entry) => entry
+            .string()?
+            .parse()
+            .map_err(|_| entry.value_pos.error("invalid method"))?,
+        None => Method::Get,
+    };
+
+    let file = fields.take("file");
+    let status = fields.take("status");
+    let location = fields.take("location");
+    let action = match (file, status) {
+        (Some(file), None) => {
+            if let Some(location) = location {
+                return Err(location
+                    .key_pos
+                    .error("'location' needs a status route, not a file route"));
+            }
+            RouteAction::File(file.file(base)?)
+        }
+        (None, Some(status)) => RouteAction::Status {
+            status: status.status()?,
+            location: location.map(|entry| entry.string()).transpose()?,
+        },
+        (Some(_), Some(status)) => {
+            return Err(status
+                .key_pos
+                .error("a route has either 'file' or 'status', not both"));
+        }
+        (None, None) => return Err(pos.error("[[route]] needs 'file' or 'status'")),
+    };
+    Ok(RouteConfig {
+        method,
+        path,
+        action,
+    })
+}
+
+/// An error reading a config.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The config is malformed or has an invalid value. line and column
+    /// count from 1.
+    Invalid {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Invalid {
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{}: {}", line, column, message),
+        }
+    }
+}
+
+impl Error for ConfigError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ConfigError::Io(e) => Some(e),
+            ConfigError::Invalid { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Pos {
+    line: usize,
+    column: usize,
+}
+
+impl Pos {
+    fn error(self, message: impl Into<String>) -> ConfigError {
+        ConfigError::Invalid {
+            line: self.line,
+            column: self.column,
+            message: message.into(),
+        }
+    }
+}
+
+/// A table from the document. Keys before the first header are in a table
+/// with an empty name.
+struct Table {
+    name: String,
+    array: bool,
+    pos: Pos,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    key: String,
+    key_pos: Pos,
+    value: Value,
+    value_pos: Pos,
+}
+
+enum Value {
+    String(String),
+    Integer(i64),
+    Boolean(bool),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Boolean(_) => "a boolean",
+        }
+    }
+}
+
+impl Entry {
+    fn mismatch(&self, expected: &str) -> ConfigError {
+        self.value_pos.error(format!(
+            "'{}' must be {}, not {}",
+            self.key,
+            expected,
+            self.value.kind()
+        ))
+    }
+
+    fn string(&self) -> Result<String, ConfigError> {
+        match &self.value {
+            Value::String(s) => Ok(s.clone()),
+            _ => Err(self.mismatch("a string")),
+        }
+    }
+
+    fn boolean(&self) -> Result<bool, ConfigError> {
+        match self.value {
+            Value::Boolean(b) => Ok(b),
+            _ => Err(self.mismatch("a boolean")),
+        }
+    }
+
+    /// Returns the value if it is an integer of at least min.
+    fn integer(&self, min: i64) -> Result<i64, ConfigError> {
+        match self.value {
+            Value::Integer(n) if n >= min => Ok(n),
+            Value::Integer(_) => Err(self
+                .value_pos
+                .error(format!("'{}' must be at least {}", self.key, min))),
+            _ => Err(self.mismatch("an integer")),
+        }
+    }
+
+    fn positive(&self) -> Result<usize, ConfigError> {
+        let n = self.integer(1)?;
+        usize::try_from(n).map_err(|_| self.value_pos.error(format!("'{}' is too large", self.key)))
+    }
+
+    fn status(&self) -> Result<StatusCode, ConfigError> {
+        let code = self.integer(100)?;
+        u16::try_from(code)
+            .ok()
+            .and_then(StatusCode::from_u16)
+            .ok_or_else(|| {
+                self.value_pos
+                    .error(format!("{} is not a known status code", code))
+            })
+    }
+
+    fn address(&self) -> Result<String, ConfigError> {
+        let addr = self.string()?;
+        match addr.to_socket_addrs().map(|mut addrs| addrs.next()) {
+            Ok(Some(_)) => Ok(addr),
+            Ok(None) => Err(self
+                .value_pos
+                .error(format!("{:?} resolves to no address", addr))),
+            Err(e) => Err(self
+                .value_pos
+                .error(format!("invalid address {:?}: {}", addr, e))),
+        }
+    }
+
+    fn file(&self, base: &Path) -> Result<PathBuf, ConfigError> {
+        self.path(base, false)
+    }
+
+    fn dir(&self, base: &Path) -> Result<PathBuf, ConfigError> {
+        self.path(base, true)
+    }
+
+    /// Resolves the value against base and checks it names an existing
+    /// file or directory.
+    fn path(&self, base: &Path, dir: bool) -> Result<PathBuf, ConfigError> {
+        let path = base.join(self.string()?);
+        match fs::metadata(&path) {
+            Ok(metadata) if metadata.is_dir() == dir => Ok(path),
+            Ok(_) => Err(self.value_pos.error(format!(
+                "{} is not a {}",
+                path.display(),
+                if dir { "directory" } else { "file" }
+            ))),
+            Err(e) => Err(self.value_pos.error(format!("{}: {}", path.display(), e))),
+        }
+    }
+}
+
+/// The entries of a table, taken out one key at a time so that any left
+/// over can be reported as unknown.
+struct Fields {
+    entries: Vec<Entry>,
+}
+
+impl Fields {
+    fn new(entries: Vec<Entry>) -> Fields {
+        Fields { entries }
+    }
+
+    fn take(&mut self, key: &str) -> Option<Entry> {
+        let i = self.entries.iter().position(|e| e.key == key)?;
+        Some(self.entries.remove(i))
+    }
+
+    fn require(&mut self, key: &str, table: Pos) -> Result<Entry, ConfigError> {
+        self.take(key)
+            .ok_or_else(|| table.error(format!("missing '{}'", key)))
+    }
+
+    fn take_all(&mut self) -> Vec<Entry> {
+        std::mem::take(&mut self.entries)
+    }
+
+    fn finish(self, table: &str) -> Result<(), ConfigError> {
+        match self.entries.first() {
+            None => Ok(()),
+            Some(entry) if table.is_empty() => Err(entry
+                .key_pos
+                .error(format!("'{}' must be inside a table", entry.key))),
+            Some(entry) => Err(entry
+                .key_pos
+                .error(format!("unknown key '{}' in [{}]", entry.key, table))),
+        }
+    }
+}
+
+/// Splits the text into tables, checking the syntax but not the meaning.
+fn parse_document(text: &str) -> Result<Vec<Table>, ConfigError> {
+    let mut tables = vec![Table {
+        name: String::new(),
+        array: false,
+        pos: Pos { line: 1, column: 1 },
+        entries: Vec::new(),
+    }];
+    for (i, line) in text.lines().enumerate() {
+        let mut cursor = Cursor {
+            line: i + 1,
+            text: line,
+            offset: 0,
+        };
+        cursor.skip_space();
+        if cursor.at_end() {
+            continue;
+        }
+
+        if cursor.peek() == Some('[') {
+            let pos = cursor.pos();
+            let array = cursor.eat("[[") || !cursor.eat("[");
+            cursor.skip_space();
+            let (name, _) = cursor.key()?;
+            cursor.skip_space();
+            if !cursor.eat(if array { "]]" } else { "]" }) {
+                return Err(cursor.pos().error("expected ']' to end the table header"));
+            }
+            cursor.end()?;
+            if !array && tables.iter().any(|t| t.name == name) {
+                return Err(pos.error(format!("table [{}] is defined twice", name)));
+            }
+            tables.push(Table {
+                name,
+                array,
+                pos,
+                entries: Vec::new(),
+            });
+            continue;
+        }
+
+        let (key, key_pos) = cursor.key()?;
+        cursor.skip_space();
+        if !cursor.eat("=") {
+            return Err(cursor.pos().error("expected '=' after the key"));
+        }
+        cursor.skip_space();
+        let value_pos = cursor.pos();
+        let value = cursor.value()?;
+        cursor.end()?;
+
+        let table = tables.last_mut().expect("the root table is always there");
+        if table.entries.iter().any(|e| e.key == key) {
+            return Err(key_pos.error(format!("duplicate key '{}'", key)));
+        }
+        table.entries.push(Entry {
+            key,
+            key_pos,
+            value,
+            value_pos,
+        });
+    }
+    Ok(tables)
+}
+
+/// Reads tokens from one line.
+struct Cursor<'a> {
+    line: usize,
+    text: &'a str,
+    /// Byte offset of the next character.
+    offset: usize,
+}
+
+impl Cursor<'_> {
+    fn pos(&self) -> Pos {
+        Pos {
+            line: self.line,
+            column: self.text[..self.offset].chars().count() + 1,
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.text[self.offset..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.offset += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.offset += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(' ' | '\t')) {
+            self.offset += 1;
+        }
+    }
+
+    /// Returns true at the end of the line or the start of a comment.
+    fn at_end(&self) -> bool {
+        matches!(self.peek(), None | Some('#'))
+    }
+
+    /// Checks nothing but space or a comment is left on the line.
+    fn end(&mut self) -> Result<(), ConfigError> {
+        self.skip_space();
+        if self.at_end() {
+            Ok(())
+        } else {
+            Err(self.pos().error("expected the end of the line"))
+        }
+    }
+
+    /// Reads a bare or quoted key.
+    fn key(&mut self) -> Result<(String, Pos), ConfigError> {
+        let pos = self.pos();
+        if self.peek() == Some('"') {
+            return Ok((self.string()?, pos));
+        }
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+            .unwrap_or(self.rest().len());
+        if len == 0 {
+            return Err(pos.error("expected a key"));
+        }
+        let key = self.rest()[..len].to_string();
+        self.offset += len;
+        Ok((key, pos))
+    }
+
+    fn value(&mut self) -> Result<Value, ConfigError> {
+        let pos = self.pos();
+        match self.peek() {
+            Some('"') => return Ok(Value::String(self.string()?)),
+            Some('0'..='9' | '+' | '-') => return self.integer(),
+            _ => {}
+        }
+        if self.eat("true") {
+            Ok(Value::Boolean(true))
+        } else if self.eat("false") {
+            Ok(Value::Boolean(false))
+        } else {
+            Err(pos.error("expected a string, integer or boolean"))
+        }
+    }
+
+    fn integer(&mut self) -> Result<Value, ConfigError> {
+        let pos = self.pos();
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '_' | '+' | '-')))
+            .unwrap_or(self.rest().len());
+        let token = &self.rest()[..len];
+        // Underscores may only separate digits, as in 1_000_000.
+        let digits = token.trim_start_matches(['+', '-']);
+        let valid = !digits.is_empty()
+            && !digits.starts_with('_')
+            && !digits.ends_with('_')
+            && !digits.contains("__")
+            && !digits.contains(['+', '-']);
+        let n = token
+            .replace('_', "")
+            .parse()
+            .ok()
+            .filter(|_| valid)
+            .ok_or_else(|| pos.error(format!("invalid integer '{}'", token)))?;
+        self.offset += len;
+        Ok(Value::Integer(n))
+    }
+
+    /// Reads a basic string. Supports the escapes `\"`, `\\`, `\n`, `\t`,
+    /// `\r` and `\uXXXX`.
+    fn string(&mut self) -> Result<String, ConfigError> {
+        let start = self.pos();
+        self.bump();
+        let mut s = String::new();
+        loop {
+            let pos = self.pos();
+            match self.bump() {
+                None => return Err(start.error("unterminated string")),
+                Some('"') => return Ok(s),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('u') => {
+                            let hex = self.rest().get(..4).unwrap_or("");
+                            let c = u32::from_str_radix(hex, 16)
+                                .ok()
+                                .filter(|_| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                                .and_then(char::from_u32)
+                                .ok_or_else(|| pos.error("invalid \\u escape"))?;
+                            self.offset += 4;
+                            c
+                        }
+                        _ => return Err(pos.error("invalid escape")),
+                    };
+                    s.push(c);
+                }
+                Some(c) if c.is_control() && c != '\t' => {
+                    return Err(pos.error("control character in string"));
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// Returns the line, column and message of the error text gives.
+    fn error(text: &str) -> (usize, usize, String) {
+        match Config::parse(text) {
+            Err(ConfigError::Invalid {
+                line,
+                column,
+                message,
+            }) => (line, column, message),
+            other => panic!("{:?} gave {:?}", text, other),
+        }
+    }
+
+    fn assert_error(text: &str, line: usize, column: usize, message: &str) {
+        let error = error(text);
+        assert_eq!(
+            (error.0, error.1),
+            (line, column),
+            "{:?}: {}",
+            text,
+            error.2
+        );
+        assert!(error.2.contains(message), "{:?} gave {:?}", text, error.2);
+    }
+
+    #[test]
+    fn empty_config_is_the_default() {
+        assert_eq!(Config::parse("").unwrap(), Config::default());
+        assert_eq!(
+            Config::parse("# nothing\n\n   \t# here\n").unwrap(),
+            Config::default()
+        );
+    }
+
+    #[test]
+    fn reads_settings() {
+        let config = Config::parse(
+            "[server]\nworkers = 1_000 # comment\ncompression = false\naccess_log = true\n\
+             [[listener]]\nbind = \"127.0.0.1:8080\"\n\
+             [[listener]]\n\"bind\" = \"127.0.0.1:8081\"\n\
+             [[route]]\npath = \"/old/*rest\"\nmethod = \"POST\"\nstatus = 308\nlocation = \"/new\"\n\
+             [mime_types]\nmd = \"text/markdown\"\n\
+             [limits]\nmax_body = +42\n\
+             [keep_alive]\nidle_timeout = 0\nmax_requests = 3\n",
+        )
+        .unwrap();
+        assert_eq!(config.workers, 1000);
+        assert!(!config.compression);
+        assert!(config.access_log);
+        assert_eq!(config.listeners, ["127.0.0.1:8080", "127.0.0.1:8081"]);
+        assert_eq!(
+            config.routes,
+            [RouteConfig {
+                method: Method::Post,
+                path: "/old/*rest".to_string(),
+                action: RouteAction::Status {
+                    status: StatusCode::PermanentRedirect,
+                    location: Some("/new".to_string()),
+                },
+            }]
+        );
+        assert_eq!(
+            config.mime_types.lookup(Path::new("a.md")).as_deref(),
+            Some("text/markdown; charset=utf-8")
+        );
+        assert_eq!(config.limits.max_body, 42);
+        assert_eq!(config.keep_alive.idle_timeout, Duration::ZERO);
+        assert_eq!(config.keep_alive.max_requests, 3);
+    }
+
+    #[test]
+    fn reads_strings_with_escapes() {
+        let config = Config::parse("[[route]]\npath = \"/\\u00e9\\\"\"\nstatus = 204\n").unwrap();
+        assert_eq!(config.routes[0].path, "/é\"");
+    }
+
+    #[test]
+    fn resolves_paths_against_the_config_directory() {
+        let dir = env::temp_dir().join(format!("websvr-config-{}", process::id()));
+        fs::create_dir_all(dir.join("public")).unwrap();
+        fs::write(dir.join("404.html"), "gone").unwrap();
+        fs::write(
+            dir.join("site.conf"),
+            "[[root]]\nprefix = \"/static/\"\ndir = \"public\"\nweak_etags = true\n\
+             [error_pages]\n404 = \"404.html\"\n",
+        )
+        .unwrap();
+        let config = Config::load(dir.join("site.conf"));
+        let missing = Config::load(dir.join("missing.conf"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        let config = config.unwrap();
+        assert_eq!(
+            config.roots,
+            [DocumentRoot {
+                prefix: "/static".to_string(),
+                dir: dir.join("public"),
+                precompressed: true,
+                weak_etags: true,
+            }]
+        );
+        assert_eq!(
+            config.error_pages,
+            [(StatusCode::NotFound, dir.join("404.html"))]
+        );
+        assert!(matches!(missing, Err(ConfigError::Io(_))));
+    }
+
+    #[test]
+    fn reports_syntax_errors_where_they_are() {
+        assert_error("[server", 1, 8, "expected ']'");
+        assert_error("[[listener]", 1, 11, "expected ']'");
+        assert_error("[]", 1, 2, "expected a key");
+        assert_error("\n\n  workers 4", 3, 11, "expected '='");
+        assert_error("[server]\nworkers = 4 4", 2, 13, "end of the line");
+        assert_error("[server]\nworkers = four", 2, 11, "expected a string");
+        assert_error("[server]\nworkers = 1__0", 2, 11, "invalid integer");
+        assert_error(
+            "[server]\nworkers = 99999999999999999999",
+            2,
+            11,
+            "invalid integer",
+        );
+        assert_error("[[route]]\npath = \"/a", 2, 8, "unterminated string");
+        assert_error("[[route]]\npath = \"/a\\q\"", 2, 11, "invalid escape");
+        assert_error("[[route]]\npath = \"/\\u12\"", 2, 10, "invalid \\u escape");
+        // Columns count characters, not bytes.
+        assert_error("[[route]]\npath = \"/é\" x", 2, 13, "end of the line");
+    }
+
+    #[test]
+    fn reports_structure_errors_where_they_are() {
+        assert_error("workers = 4", 1, 1, "must be inside a table");
+        assert_error("[server]\nworkers = 4\nworkers = 5", 3, 1, "duplicate key");
+        assert_error("[server]\n[limits]\n[server]", 3, 1, "defined twice");
+        assert_error("[servers]", 1, 1, "unknown table");
+        assert_error(
+            "[server]\n  threads = 4",
+            2,
+            3,
+            "unknown key 'threads' in [server]",
+        );
+        assert_error(
+            "[listener]\nbind = \"127.0.0.1:80\"",
+            1,
+            1,
+            "use [[listener]]",
+        );
+        assert_error("[[limits]]", 1, 1, "use [limits]");
+        assert_error("[[mime_types]]", 1, 1, "use [mime_types]");
+        assert_error("\n[[listener]]", 2, 1, "missing 'bind'");
+    }
+
+    #[test]
+    fn reports_invalid_values_where_they_are() {
+        assert_error("[server]\nworkers = 0", 2, 11, "at least 1");
+        assert_error(
+            "[server]\nworkers = \"4\"",
+            2,
+            11,
+            "must be an integer, not a string",
+        );
+        assert_error("[server]\ncompression = 1", 2, 15, "must be a boolean");
+        assert_error("[keep_alive]\nidle_timeout = -1", 2, 16, "at least 0");
+        assert_error("[error_pages]\n200 = \"x.html\"", 2, 1, "not a 4xx or 5xx");
+        assert_error("[mime_types]\nmd = \"markdown\"", 2, 6, "not a media type");
+        assert_error(
+            "[[route]]\npath = \"no-slash\"\nstatus = 404",
+            2,
+            8,
+            "must start with",
+        );
+        assert_error(
+            "[[route]]\npath = \"/\"\nstatus = 99",
+            3,
+            10,
+            "at least 100",
+        );
+        assert_error(
+            "[[route]]\npath = \"/\"\nstatus = 299",
+            3,
+            10,
+            "not a known status",
+        );
+        assert_error("[[route]]\npath = \"/\"", 1, 1, "needs 'file' or 'status'");
+        assert_error(
+            "[[route]]\npath = \"/\"\nmethod = \"G T\"\nstatus = 204",
+            3,
+            10,
+            "method",
+        );
+        assert_error(
+            "[[root]]\ndir = \"/\"\nprefix = \"/:id\"",
+            3,
+            10,
+            "no parameters",
+        );
+        assert_error(
+            "[[root]]\ndir = \"/\"\n[[root]]\ndir = \"/\"",
+            3,
+            1,
+            "more than one",
+        );
+        assert_error(
+            "[[root]]\ndir = \"/nonexistent/dir\"",
+            2,
+            7,
+            "/nonexistent/dir",
+        );
+        assert_error("[[listener]]\nbind = \"nowhere\"", 2, 8, "invalid address");
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    io::{self, BufReader},
+    net::TcpStream,
+    panic::{self, AssertUnwindSafe},
+    time::Duration,
+};
+
+use crate::{
+    job::panic_message, Handler, Limits, ParseError, Request, Response, ServerError, StatusCode,
+    Version,
+};
+
+/// Settings for persistent connections.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeepAlive {
+    /// How long to wait for the next request before closing the connection.
+    /// Zero means no limit.
+    pub idle_timeout: Duration,
+    /// The most requests served on one connection. The response to the last
+    /// one has `Connection: close`. Zero is treated as one.
+    pub max_requests: usize,
+}
+
+impl Default for KeepAlive {
+    fn default() -> KeepAlive {
+        KeepAlive {
+            idle_timeout: Duration::from_secs(5),
+            max_requests: 100,
+        }
+    }
+}
+
+/// Serves requests from stream with handler until the connection closes.
+///
+/// HTTP/1.1 connections stay open unless either side sends
+/// `Connection: close`; HTTP/1.0 ones only if the client sends
+/// `Connection: keep-alive`. Pipelined requests are answered in the order
+/// they arrive.
+///
+/// If handler panics, the client is sent a 500 and the connection is
+/// closed. Returns an error for such a panic, or if reading or writing
+/// fails for any reason other than the client closing the connection or
+/// going idle.
+pub fn serve_connection(
+    stream: TcpStream,
+    handler: &dyn Handler,
+    limits: &Limits,
+    keep_alive: &KeepAlive,
+) -> Result<(), ServerError> {
+    let idle_timeout = Some(keep_alive.idle_timeout).filter(|t| !t.is_zero());
+    stream.set_read_timeout(idle_timeout)?;
+    let mut reader = BufReader::new(&stream);
+    let mut writer = &stream;
+
+    for served in 1.. {
+        let mut request = match Request::read_with_limits(&mut reader, limits) {
+            Ok(request) => request,
+            Err(ParseError::ConnectionClosed) => return Ok(()),
+            Err(ParseError::Io(e)) if client_gone(&e) => return Ok(()),
+            Err(ParseError::Io(e)) => return Err(e.into()),
+            Err(e) => {
+                if let Some(status) = e.status_code() {
+                    let response = Response::new(status).with_header("Connection", "close");
+                    return written(response.write_to(&mut writer));
+                }
+                return Ok(());
+            }
+        };
+
+        let mut response =
+            match panic::catch_unwind(AssertUnwindSafe(|| handler.handle(&mut request))) {
+                Ok(response) => response,
+                Err(payload) => {
+                    let response =
+                        Response::text(StatusCode::InternalServerError, "Internal Server Error")
+                            .with_header("Connection", "close");
+                    written(response.write_for(&request, &mut writer))?;
+                    let msg = panic_message(payload.as_ref()).map(str::to_string);
+                    return Err(ServerError::HandlerPanicked(msg));
+                }
+            };
+        let keep_open = served < keep_alive.max_requests
+            && wants_keep_alive(&request)
+            && !response.headers().has_value("Connection", "close")
+            // An HTTP/1.0 body of unknown length ends when the connection
+            // closes.
+            && (request.version() >= Version::Http11 || response.body().len().is_some());
+        if !keep_open {
+            response.headers_mut().insert("Connection", "close");
+        } else if request.version() == Version::Http10 {
+            response.headers_mut().insert("Connection", "keep-alive");
+        }
+
+        written(response.write_for(&request, &mut writer))?;
+        if !keep_open {
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+fn wants_keep_alive(request: &Request) -> bool {
+    let headers = request.headers();
+    match request.version() {
+        Version::Http10 => headers.has_value("Connection", "keep-alive"),
+        Version::Http11 => !headers.has_value("Connection", "close"),
+    }
+}
+
+/// Treats a failed write to a client that has gone as success, since
+/// there's no one left to tell.
+fn written(result: io::Result<()>) -> Result<(), ServerError> {
+    match result {
+        Err(e) if !client_gone(&e) => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Returns true if e means the client went idle or hung up.
+fn client_gone(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::BrokenPipe
+    )
+}
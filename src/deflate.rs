@@ -0,0 +1,735 @@
+use std::io::{self, Read, Write};
+
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions to try before settling for the best match
+/// found so far.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+/// The most bytes a stored block can hold.
+const MAX_STORED: usize = 65_535;
+/// How much input EncodeReader and EncodeWriter compress at a time.
+const CHUNK: usize = 64 * 1024;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// The container around the DEFLATE data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    /// RFC 1952, used by `Content-Encoding: gzip`.
+    Gzip,
+    /// RFC 1950, used by `Content-Encoding: deflate`.
+    Zlib,
+}
+
+/// Compresses data in one go.
+pub(crate) fn compress(format: Format, data: &[u8]) -> Vec<u8> {
+    let mut encoder = Encoder::new(format);
+    encoder.write(data);
+    encoder.finish();
+    encoder.take_output()
+}
+
+/// A streaming encoder. Input is fed in with `write`, and compressed bytes
+/// are collected with `take_output`.
+pub(crate) struct Encoder {
+    format: Format,
+    deflater: Deflater,
+    crc: u32,
+    adler: (u32, u32),
+    size: u32,
+}
+
+impl Encoder {
+    pub(crate) fn new(format: Format) -> Encoder {
+        let mut deflater = Deflater::new();
+        match format {
+            // No file name or modification time; OS unknown.
+            Format::Gzip => deflater
+                .bits
+                .out
+                .extend_from_slice(&[0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff]),
+            // 32 KiB window, no preset dictionary, fastest-level hint.
+            Format::Zlib => deflater.bits.out.extend_from_slice(&[0x78, 0x01]),
+        }
+        Encoder {
+            format,
+            deflater,
+            crc: !0,
+            adler: (1, 0),
+            size: 0,
+        }
+    }
+
+    pub(crate) fn write(&mut self, input: &[u8]) {
+        if input.is_empty() {
+            return;
+        }
+        match self.format {
+            Format::Gzip => {
+                for &b in input {
+                    self.crc =
+                        CRC_TABLE[((self.crc ^ u32::from(b)) & 0xff) as usize] ^ (self.crc >> 8);
+                }
+                self.size = self.size.wrapping_add(input.len() as u32);
+            }
+            Format::Zlib => {
+                // 5552 is the most bytes that can be summed before the
+                // sums need reducing to stay in a u32.
+                let (mut a, mut b) = self.adler;
+                for chunk in input.chunks(5552) {
+                    for &byte in chunk {
+                        a += u32::from(byte);
+                        b += a;
+                    }
+                    a %= 65_521;
+                    b %= 65_521;
+                }
+                self.adler = (a, b);
+            }
+        }
+        self.deflater.write(input);
+    }
+
+    /// Ends the current block and pads to a byte boundary, so everything
+    /// written so far can be decoded from the output taken so far.
+    pub(crate) fn sync(&mut self) {
+        self.deflater.sync();
+    }
+
+    /// Ends the stream. Nothing may be written after this.
+    pub(crate) fn finish(&mut self) {
+        self.deflater.finish();
+        let out = &mut self.deflater.bits.out;
+        match self.format {
+            Format::Gzip => {
+                out.extend_from_slice(&(!self.crc).to_le_bytes());
+                out.extend_from_slice(&self.size.to_le_bytes());
+            }
+            Format::Zlib => {
+                let (a, b) = self.adler;
+                out.extend_from_slice(&((b << 16) | a).to_be_bytes());
+            }
+        }
+    }
+
+    /// Takes the compressed bytes produced so far.
+    pub(crate) fn take_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.deflater.bits.out)
+    }
+}
+
+/// A reader that compresses what it reads from another reader.
+pub(crate) struct EncodeReader<R> {
+    inner: R,
+    encoder: Encoder,
+    output: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> EncodeReader<R> {
+    pub(crate) fn new(inner: R, format: Format) -> EncodeReader<R> {
+        EncodeReader {
+            inner,
+            encoder: Encoder::new(format),
+            output: Vec::new(),
+            pos: 0,
+            done: false,
+        }
+    }
+}
+
+impl<R: Read> Read for EncodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.output.len() {
+            if self.done {
+                return Ok(0);
+            }
+            let mut input = Vec::with_capacity(CHUNK);
+            (&mut self.inner)
+                .take(CHUNK as u64)
+                .read_to_end(&mut input)?;
+            if input.is_empty() {
+                self.encoder.finish();
+                self.done = true;
+            } else {
+                self.encoder.write(&input);
+            }
+            self.output = self.encoder.take_output();
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A writer that compresses what is written to it into another writer.
+///
+/// Input is compressed in CHUNK sized pieces; `flush` compresses what is
+/// buffered and syncs the stream, so the client can decode everything
+/// written so far. `finish` must be called to end the stream.
+pub(crate) struct EncodeWriter<W: Write> {
+    inner: W,
+    encoder: Encoder,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> EncodeWriter<W> {
+    pub(crate) fn new(inner: W, format: Format) -> EncodeWriter<W> {
+        EncodeWriter {
+            inner,
+            encoder: Encoder::new(format),
+            buffer: Vec::new(),
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<W> {
+        self.encoder.write(&self.buffer);
+        self.encoder.finish();
+        self.inner.write_all(&self.encoder.take_output())?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+
+    fn compress_buffer(&mut self) -> io::Result<()> {
+        self.encoder.write(&self.buffer);
+        self.buffer.clear();
+        self.inner.write_all(&self.encoder.take_output())
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= CHUNK {
+            self.compress_buffer()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.compress_buffer()?;
+        self.encoder.sync();
+        self.inner.write_all(&self.encoder.take_output())?;
+        self.inner.flush()
+    }
+}
+
+/// A DEFLATE (RFC 1951) compressor.
+///
+/// Matches are found with hash chains over a 32 KiB window and written with
+/// the fixed Huffman codes, which gives up some ratio next to dynamic codes
+/// but keeps the encoder small. Each `write` becomes one block, or several
+/// stored blocks if the input doesn't compress; matches may reach back into
+/// earlier writes.
+struct Deflater {
+    /// The last WINDOW bytes of earlier input, then the input being
+    /// compressed.
+    data: Vec<u8>,
+    /// The stream position of `data[0]`.
+    base: usize,
+    /// For each hash, the latest stream position with that hash, plus one.
+    head: Vec<usize>,
+    /// For each stream position modulo WINDOW, the previous position with
+    /// the same hash, plus one.
+    prev: Vec<usize>,
+    bits: BitWriter,
+}
+
+impl Deflater {
+    fn new() -> Deflater {
+        Deflater {
+            data: Vec::new(),
+            base: 0,
+            head: vec![0; 1 << HASH_BITS],
+            prev: vec![0; WINDOW],
+            bits: BitWriter::default(),
+        }
+    }
+
+    fn write(&mut self, input: &[u8]) {
+        let start = self.data.len();
+        self.data.extend_from_slice(input);
+
+        let mut tokens = Vec::new();
+        // The block header and the end-of-block code.
+        let mut bits = 3 + 7;
+        let mut i = start;
+        while i < self.data.len() {
+            let (length, distance) = self.longest_match(i);
+            let token = if length >= MIN_MATCH {
+                Token::Match { length, distance }
+            } else {
+                Token::Literal(self.data[i])
+            };
+            let advance = length.max(1);
+            for j in i..i + advance {
+                self.insert(j);
+            }
+            bits += token.bits();
+            tokens.push(token);
+            i += advance;
+        }
+
+        // Each stored block has a 5 byte header, and the first may need up
+        // to 7 bits of padding.
+        let stored_bits = (input.len() + 5 * input.len().div_ceil(MAX_STORED)) * 8 + 7;
+        if stored_bits < bits {
+            for chunk in self.data[start..].chunks(MAX_STORED) {
+                // BFINAL = 0, BTYPE = 00 (stored).
+                self.bits.write(0b000, 3);
+                self.bits.flush();
+                let len = chunk.len() as u16;
+                self.bits.out.extend_from_slice(&len.to_le_bytes());
+                self.bits.out.extend_from_slice(&(!len).to_le_bytes());
+                self.bits.out.extend_from_slice(chunk);
+            }
+        } else {
+            // BFINAL = 0, BTYPE = 01 (fixed Huffman codes).
+            self.bits.write(0b010, 3);
+            for token in tokens {
+                match token {
+                    Token::Literal(byte) => self.write_symbol(u16::from(byte)),
+                    Token::Match { length, distance } => self.write_match(length, distance),
+                }
+            }
+            self.write_symbol(256);
+        }
+
+        if self.data.len() > WINDOW {
+            let drop = self.data.len() - WINDOW;
+            self.data.drain(..drop);
+            self.base += drop;
+        }
+    }
+
+    /// Writes an empty stored block, which pads the output to a byte
+    /// boundary (the zlib "sync flush").
+    fn sync(&mut self) {
+        // BFINAL = 0, BTYPE = 00 (stored), then LEN = 0 and NLEN = !0.
+        self.bits.write(0b000, 3);
+        self.bits.flush();
+        self.bits.out.extend_from_slice(&[0, 0, 0xff, 0xff]);
+    }
+
+    fn finish(&mut self) {
+        // An empty final block.
+        self.bits.write(0b011, 3);
+        self.write_symbol(256);
+        self.bits.flush();
+    }
+
+    fn hash(&self, i: usize) -> usize {
+        let d = &self.data[i..i + MIN_MATCH];
+        let h = (u32::from(d[0]) << 16 | u32::from(d[1]) << 8 | u32::from(d[2]))
+            .wrapping_mul(0x9e37_79b1);
+        (h >> (32 - HASH_BITS)) as usize
+    }
+
+    fn insert(&mut self, i: usize) {
+        if i + MIN_MATCH > self.data.len() {
+            return;
+        }
+        let h = self.hash(i);
+        let pos = self.base + i;
+        self.prev[pos % WINDOW] = self.head[h];
+        self.head[h] = pos + 1;
+    }
+
+    /// Returns the length and distance of the longest earlier match for the
+    /// bytes at data[i], or a length of 0.
+    fn longest_match(&self, i: usize) -> (usize, usize) {
+        if i + MIN_MATCH > self.data.len() {
+            return (0, 0);
+        }
+        let pos = self.base + i;
+        let max = MAX_MATCH.min(self.data.len() - i);
+        let (mut best, mut distance) = (0, 0);
+        let mut candidate = self.head[self.hash(i)];
+        for _ in 0..MAX_CHAIN {
+            if candidate == 0 {
+                break;
+            }
+            let earlier = candidate - 1;
+            if earlier < self.base || pos - earlier > WINDOW {
+                break;
+            }
+            let j = earlier - self.base;
+            let length = (0..max)
+                .take_while(|&k| self.data[j + k] == self.data[i + k])
+                .count();
+            if length > best {
+                best = length;
+                distance = pos - earlier;
+                if length == max {
+                    break;
+                }
+            }
+            let next = self.prev[earlier % WINDOW];
+            // A slot reused by a newer position ends the chain.
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        (best, distance)
+    }
+
+    fn write_match(&mut self, length: usize, distance: usize) {
+        let code = length_code(length);
+        self.write_symbol(257 + code as u16);
+        self.bits.write(
+            (length - usize::from(LENGTH_BASE[code])) as u32,
+            u32::from(LENGTH_EXTRA[code]),
+        );
+        let code = distance_code(distance);
+        self.bits.write_code(code as u32, 5);
+        self.bits.write(
+            (distance - usize::from(DIST_BASE[code])) as u32,
+            u32::from(DIST_EXTRA[code]),
+        );
+    }
+
+    /// Writes a literal/length symbol with its fixed Huffman code
+    /// (RFC 1951 3.2.6).
+    fn write_symbol(&mut self, symbol: u16) {
+        let symbol = u32::from(symbol);
+        match symbol {
+            0..=143 => self.bits.write_code(0x30 + symbol, 8),
+            144..=255 => self.bits.write_code(0x190 + symbol - 144, 9),
+            256..=279 => self.bits.write_code(symbol - 256, 7),
+            _ => self.bits.write_code(0xc0 + symbol - 280, 8),
+        }
+    }
+}
+
+enum Token {
+    Literal(u8),
+    Match { length: usize, distance: usize },
+}
+
+impl Token {
+    /// The number of bits the token takes with the fixed codes.
+    fn bits(&self) -> usize {
+        match *self {
+            Token::Literal(byte) => 8 + usize::from(byte >= 144),
+            Token::Match { length, distance } => {
+                let length_code = length_code(length);
+                // Length symbols from 280 up have 8 bit codes, the rest 7.
+                let symbol_bits = 7 + usize::from(257 + length_code >= 280);
+                let distance_code = distance_code(distance);
+                symbol_bits
+                    + usize::from(LENGTH_EXTRA[length_code])
+                    + 5
+                    + usize::from(DIST_EXTRA[distance_code])
+            }
+        }
+    }
+}
+
+fn length_code(length: usize) -> usize {
+    LENGTH_BASE
+        .iter()
+        .rposition(|&b| usize::from(b) <= length)
+        .unwrap()
+}
+
+fn distance_code(distance: usize) -> usize {
+    DIST_BASE
+        .iter()
+        .rposition(|&b| usize::from(b) <= distance)
+        .unwrap()
+}
+
+/// Packs bits into bytes, least significant bit first.
+#[derive(Default)]
+struct BitWriter {
+    out: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u32, count: u32) {
+        self.bits |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.out.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes most significant bit first.
+    fn write_code(&mut self, code: u32, length: u32) {
+        self.write(code.reverse_bits() >> (32 - length), length);
+    }
+
+    /// Pads to a byte boundary.
+    fn flush(&mut self) {
+        if self.count > 0 {
+            self.out.push(self.bits as u8);
+            self.bits = 0;
+            self.count = 0;
+        }
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xedb8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads bits least significant first, as DEFLATE packs them.
+    struct BitReader<'a> {
+        data: &'a [u8],
+        pos: usize,
+        bit: u32,
+    }
+
+    impl BitReader<'_> {
+        fn bits(&mut self, count: u32) -> u32 {
+            let mut value = 0;
+            for i in 0..count {
+                value |= u32::from(self.data[self.pos] >> self.bit & 1) << i;
+                self.bit += 1;
+                if self.bit == 8 {
+                    self.pos += 1;
+                    self.bit = 0;
+                }
+            }
+            value
+        }
+
+        /// Reads a Huffman code of count bits, most significant first.
+        fn code(&mut self, count: u32) -> u32 {
+            (0..count).fold(0, |code, _| code << 1 | self.bits(1))
+        }
+
+        fn align(&mut self) {
+            if self.bit > 0 {
+                self.pos += 1;
+                self.bit = 0;
+            }
+        }
+
+        /// Decodes a literal/length symbol with the fixed codes.
+        fn symbol(&mut self) -> u16 {
+            let code = self.code(7);
+            if code <= 0x17 {
+                return 256 + code as u16;
+            }
+            let code = code << 1 | self.bits(1);
+            match code {
+                0x30..=0xbf => (code - 0x30) as u16,
+                0xc0..=0xc7 => (280 + code - 0xc0) as u16,
+                _ => (144 + (code << 1 | self.bits(1)) - 0x190) as u16,
+            }
+        }
+    }
+
+    /// Decodes a raw DEFLATE stream made of stored and fixed Huffman
+    /// blocks, which is all Deflater writes. Returns the data and the
+    /// number of bytes the stream took up.
+    fn inflate(data: &[u8]) -> (Vec<u8>, usize) {
+        let mut reader = BitReader {
+            data,
+            pos: 0,
+            bit: 0,
+        };
+        let mut out = Vec::new();
+        loop {
+            let last = reader.bits(1) == 1;
+            match reader.bits(2) {
+                0 => {
+                    reader.align();
+                    let header = &data[reader.pos..reader.pos + 4];
+                    let len = u16::from_le_bytes([header[0], header[1]]);
+                    assert_eq!(!len, u16::from_le_bytes([header[2], header[3]]));
+                    reader.pos += 4;
+                    out.extend_from_slice(&data[reader.pos..reader.pos + usize::from(len)]);
+                    reader.pos += usize::from(len);
+                }
+                1 => loop {
+                    let symbol = reader.symbol();
+                    match symbol {
+                        0..=255 => out.push(symbol as u8),
+                        256 => break,
+                        _ => {
+                            let code = usize::from(symbol - 257);
+                            let length = usize::from(LENGTH_BASE[code])
+                                + reader.bits(u32::from(LENGTH_EXTRA[code])) as usize;
+                            let code = reader.code(5) as usize;
+                            let distance = usize::from(DIST_BASE[code])
+                                + reader.bits(u32::from(DIST_EXTRA[code])) as usize;
+                            assert!(distance <= out.len().min(WINDOW), "distance too far back");
+                            for _ in 0..length {
+                                out.push(out[out.len() - distance]);
+                            }
+                        }
+                    }
+                },
+                btype => panic!("unexpected block type {}", btype),
+            }
+            if last {
+                reader.align();
+                return (out, reader.pos);
+            }
+        }
+    }
+
+    /// Decodes a gzip stream, checking its header and trailer.
+    fn gunzip(data: &[u8]) -> Vec<u8> {
+        assert_eq!(data[..3], [0x1f, 0x8b, 8]);
+        let (out, used) = inflate(&data[10..]);
+        let trailer = &data[10 + used..];
+        assert_eq!(trailer.len(), 8);
+        let crc = !out.iter().fold(!0u32, |crc, &b| {
+            CRC_TABLE[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+        });
+        assert_eq!(trailer[..4], crc.to_le_bytes());
+        assert_eq!(trailer[4..], (out.len() as u32).to_le_bytes());
+        out
+    }
+
+    /// Decodes a zlib stream, checking its header and trailer.
+    fn unzlib(data: &[u8]) -> Vec<u8> {
+        assert_eq!(u16::from_be_bytes([data[0], data[1]]) % 31, 0);
+        let (out, used) = inflate(&data[2..]);
+        let trailer = &data[2 + used..];
+        let (a, b) = out.iter().fold((1u32, 0u32), |(a, b), &byte| {
+            let a = (a + u32::from(byte)) % 65_521;
+            (a, (b + a) % 65_521)
+        });
+        assert_eq!(trailer, ((b << 16) | a).to_be_bytes());
+        out
+    }
+
+    fn round_trip(data: &[u8]) {
+        assert_eq!(gunzip(&compress(Format::Gzip, data)), data);
+        assert_eq!(unzlib(&compress(Format::Zlib, data)), data);
+    }
+
+    /// Bytes from a xorshift generator, which don't compress.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut x = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                x ^= x << 13;
+                x ^= x >> 7;
+                x ^= x << 17;
+                x as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_empty_input() {
+        round_trip(b"");
+    }
+
+    #[test]
+    fn round_trips_incompressible_input() {
+        // More than one stored block's worth.
+        let data = noise(MAX_STORED * 2 + 100);
+        round_trip(&data);
+        let compressed = compress(Format::Gzip, &data);
+        assert!(compressed.len() < data.len() + 64);
+    }
+
+    #[test]
+    fn round_trips_input_larger_than_window() {
+        let data: Vec<u8> = (0..WINDOW * 4)
+            .map(|i| b"the quick brown fox jumps over the lazy dog "[i % 44] ^ (i / 997) as u8)
+            .collect();
+        round_trip(&data);
+        assert!(compress(Format::Gzip, &data).len() < data.len() / 2);
+    }
+
+    #[test]
+    fn matches_reach_into_earlier_writes() {
+        let line = b"a line that is long enough to be worth matching\n";
+        let mut encoder = Encoder::new(Format::Gzip);
+        encoder.write(line);
+        let first = encoder.take_output().len();
+        encoder.write(line);
+        let second = encoder.take_output().len();
+        assert!(second < first / 4, "{} bytes after {}", second, first);
+
+        encoder.finish();
+
+        let mut encoder = Encoder::new(Format::Gzip);
+        let mut stream = Vec::new();
+        for piece in [&line[..10], &line[10..], &line[..], &line[..]] {
+            encoder.write(piece);
+            stream.extend(encoder.take_output());
+        }
+        encoder.finish();
+        stream.extend(encoder.take_output());
+        assert_eq!(gunzip(&stream), line.repeat(3));
+    }
+
+    #[test]
+    fn reader_and_writer_round_trip() {
+        let data: Vec<u8> = noise(CHUNK)
+            .into_iter()
+            .chain(b"abc".repeat(CHUNK))
+            .collect();
+
+        let mut compressed = Vec::new();
+        EncodeReader::new(&data[..], Format::Zlib)
+            .read_to_end(&mut compressed)
+            .unwrap();
+        assert_eq!(unzlib(&compressed), data);
+
+        let mut writer = EncodeWriter::new(Vec::new(), Format::Gzip);
+        for piece in data.chunks(1000) {
+            writer.write_all(piece).unwrap();
+        }
+        writer.flush().unwrap();
+        writer.write_all(b"after a flush").unwrap();
+        let compressed = writer.finish().unwrap();
+        let mut expected = data.clone();
+        expected.extend_from_slice(b"after a flush");
+        assert_eq!(gunzip(&compressed), expected);
+    }
+}
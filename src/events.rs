@@ -0,0 +1,318 @@
+use std::{fmt, io, time::Duration};
+
+use crate::ShutdownMode;
+
+/// Receives events from a ThreadPool as its workers and jobs come and go.
+///
+/// Closures taking a `&PoolEvent` implement this trait, as does `StderrLogger`.
+/// A pool with no listener is silent.
+pub trait EventListener: Send + Sync {
+    fn on_event(&self, event: &PoolEvent<'_>);
+}
+
+impl<F> EventListener for F
+where
+    F: Fn(&PoolEvent<'_>) + Send + Sync,
+{
+    fn on_event(&self, event: &PoolEvent<'_>) {
+        self(event)
+    }
+}
+
+/// Something that happened inside a ThreadPool.
+#[derive(Debug)]
+pub enum PoolEvent<'a> {
+    /// A worker thread started.
+    WorkerStarted { worker: usize },
+    /// A worker thread is exiting.
+    WorkerExited { worker: usize, reason: ExitReason },
+    /// A worker thread could not be spawned to grow the pool or replace a dead worker,
+    /// or its `on_start` hook panicked.
+    WorkerSpawnFailed { error: &'a io::Error },
+    /// A worker took a job off the queue.
+    JobStarted { worker: usize, queue_wait: Duration },
+    /// A worker finished running a job.
+    JobFinished {
+        worker: usize,
+        run_time: Duration,
+        panicked: bool,
+    },
+    /// `execute` dropped a job because the queue was full.
+    JobRejected,
+    /// The pool started shutting down.
+    ShutdownStarted { mode: ShutdownMode },
+    /// The pool finished shutting down. busy lists the workers that were
+    /// still running a job at the deadline.
+    ShutdownFinished { busy: &'a [usize] },
+}
+
+/// Why a worker thread exited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitReason {
+    /// The pool shut down.
+    Shutdown,
+    /// The worker was idle past the keep-alive and the pool was above its minimum size.
+    Retired,
+    /// The thread unwound outside of a job and is being replaced.
+    Panicked,
+}
+
+impl PoolEvent<'_> {
+    /// How noteworthy the event is.
+    pub fn level(&self) -> Level {
+        match self {
+            PoolEvent::JobStarted { .. } => Level::Debug,
+            PoolEvent::JobFinished { panicked, .. } => {
+                if *panicked {
+                    Level::Warn
+                } else {
+                    Level::Debug
+                }
+            }
+            PoolEvent::WorkerStarted { .. } | PoolEvent::ShutdownStarted { .. } => Level::Info,
+            PoolEvent::WorkerExited { reason, .. } => match reason {
+                ExitReason::Panicked => Level::Warn,
+                ExitReason::Shutdown | ExitReason::Retired => Level::Info,
+            },
+            PoolEvent::ShutdownFinished { busy } => {
+                if busy.is_empty() {
+                    Level::Info
+                } else {
+                    Level::Warn
+                }
+            }
+            PoolEvent::WorkerSpawnFailed { .. } | PoolEvent::JobRejected => Level::Warn,
+        }
+    }
+}
+
+impl fmt::Display for PoolEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolEvent::WorkerStarted { worker } => write!(f, "worker {} started", worker),
+            PoolEvent::WorkerExited { worker, reason } => {
+                write!(f, "worker {} exited: {:?}", worker, reason)
+            }
+            PoolEvent::WorkerSpawnFailed { error } => {
+                write!(f, "failed to spawn worker: {}", error)
+            }
+            PoolEvent::JobStarted { worker, queue_wait } => {
+                write!(
+                    f,
+                    "worker {} started a job after {:?} queued",
+                    worker, queue_wait
+                )
+            }
+            PoolEvent::JobFinished {
+                worker,
+                run_time,
+                panicked,
+            } => {
+                let outcome = if *panicked { "panicked" } else { "finished" };
+                write!(f, "worker {} job {} after {:?}", worker, outcome, run_time)
+            }
+            PoolEvent::JobRejected => write!(f, "job queue is full, rejected a job"),
+            PoolEvent::ShutdownStarted { mode } => write!(f, "shutting down: {:?}", mode),
+            PoolEvent::ShutdownFinished { busy } => {
+                if busy.is_empty() {
+                    write!(f, "shut down")
+                } else {
+                    write!(f, "shutdown timed out with workers {:?} still busy", busy)
+                }
+            }
+        }
+    }
+}
+
+/// Severity of a PoolEvent, from least to most noteworthy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Debug,
+    Info,
+    Warn,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Debug => "DEBUG",
+            Level::Info => "INFO",
+            Level::Warn => "WARN",
+        };
+        f.pad(name)
+    }
+}
+
+/// An EventListener that writes events at or above a level to stderr.
+#[derive(Debug, Clone, Copy)]
+pub struct StderrLogger {
+    level: Level,
+}
+
+impl StderrLogger {
+    pub fn new(level: Level) -> StderrLogger {
+        StderrLogger { level }
+    }
+}
+
+impl EventListener for StderrLogger {
+    fn on_event(&self, event: &PoolEvent<'_>) {
+        let level = event.level();
+        if level >= self.level {
+            eprintln!("[{:<5}] {}", level, event);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{wait_until, PATIENCE},
+        ThreadPoolBuilder,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Names an event without the timings, which vary from run to run.
+    fn describe(event: &PoolEvent<'_>) -> String {
+        match event {
+            PoolEvent::JobStarted { worker, .. } => format!("worker {} started a job", worker),
+            PoolEvent::JobFinished {
+                worker, panicked, ..
+            } => format!("worker {} job panicked: {}", worker, panicked),
+            event => event.to_string(),
+        }
+    }
+
+    #[test]
+    fn listener_sees_a_pool_from_start_to_shutdown() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .panic_handler(|_| {})
+            .event_listener({
+                let seen = Arc::clone(&seen);
+                move |event: &PoolEvent<'_>| seen.lock().unwrap().push(describe(event))
+            })
+            .build()
+            .unwrap();
+        pool.execute(|| {});
+        pool.execute(|| panic!("reported"));
+        wait_until("both jobs finish", || seen.lock().unwrap().len() == 5);
+        pool.shutdown(ShutdownMode::Drain, PATIENCE).unwrap();
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                "worker 0 started",
+                "worker 0 started a job",
+                "worker 0 job panicked: false",
+                "worker 0 started a job",
+                "worker 0 job panicked: true",
+                "shutting down: Drain",
+                "worker 0 exited: Shutdown",
+                "shut down",
+            ]
+        );
+    }
+
+    #[test]
+    fn events_have_levels() {
+        let error = io::Error::other("no threads left");
+        let levels = [
+            (
+                PoolEvent::JobStarted {
+                    worker: 0,
+                    queue_wait: Duration::ZERO,
+                },
+                Level::Debug,
+            ),
+            (
+                PoolEvent::JobFinished {
+                    worker: 0,
+                    run_time: Duration::ZERO,
+                    panicked: false,
+                },
+                Level::Debug,
+            ),
+            (
+                PoolEvent::JobFinished {
+                    worker: 0,
+                    run_time: Duration::ZERO,
+                    panicked: true,
+                },
+                Level::Warn,
+            ),
+            (PoolEvent::WorkerStarted { worker: 0 }, Level::Info),
+            (
+                PoolEvent::WorkerExited {
+                    worker: 0,
+                    reason: ExitReason::Retired,
+                },
+                Level::Info,
+            ),
+            (
+                PoolEvent::WorkerExited {
+                    worker: 0,
+                    reason: ExitReason::Panicked,
+                },
+                Level::Warn,
+            ),
+            (PoolEvent::WorkerSpawnFailed { error: &error }, Level::Warn),
+            (PoolEvent::JobRejected, Level::Warn),
+            (
+                PoolEvent::ShutdownStarted {
+                    mode: ShutdownMode::Cancel,
+                },
+                Level::Info,
+            ),
+            (PoolEvent::ShutdownFinished { busy: &[] }, Level::Info),
+            (PoolEvent::ShutdownFinished { busy: &[2] }, Level::Warn),
+        ];
+        for (event, level) in levels {
+            assert_eq!(event.level(), level, "{:?}", event);
+        }
+    }
+
+    #[test]
+    fn events_display_what_happened() {
+        let error = io::Error::other("no threads left");
+        assert_eq!(
+            PoolEvent::WorkerSpawnFailed { error: &error }.to_string(),
+            "failed to spawn worker: no threads left"
+        );
+        assert_eq!(
+            PoolEvent::JobStarted {
+                worker: 1,
+                queue_wait: Duration::from_millis(3)
+            }
+            .to_string(),
+            "worker 1 started a job after 3ms queued"
+        );
+        assert_eq!(
+            PoolEvent::JobFinished {
+                worker: 2,
+                run_time: Duration::from_micros(5),
+                panicked: true
+            }
+            .to_string(),
+            "worker 2 job panicked after 5µs"
+        );
+        assert_eq!(
+            PoolEvent::JobRejected.to_string(),
+            "job queue is full, rejected a job"
+        );
+        assert_eq!(
+            PoolEvent::ShutdownFinished { busy: &[0, 3] }.to_string(),
+            "shutdown timed out with workers [0, 3] still busy"
+        );
+    }
+
+    #[test]
+    fn levels_are_ordered_and_padded() {
+        assert!(Level::Debug < Level::Info && Level::Info < Level::Warn);
+        assert_eq!(format!("[{:<5}]", Level::Info), "[INFO ]");
+        assert_eq!(format!("[{:<5}]", Level::Debug), "[DEBUG]");
+        assert_eq!(Level::Warn.to_string(), "WARN");
+    }
+}
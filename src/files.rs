@@ -0,0 +1,307 @@
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    compression::{add_vary, negotiate},
+    conditional::Validators,
+    httpdate,
+    range::{self, Multipart, Ranges},
+    router::percent_decode,
+    Handler, Method, MimeTypes, Request, Response, StatusCode,
+};
+
+/// A Handler that serves files from a document root.
+///
+/// The file is looked up from the path captured by a `*path` wildcard if the
+/// route has one, and from the whole request path otherwise, so
+/// `/static/*path` with a root of `public` serves `/static/a.css` from
+/// `public/a.css`. A directory is served by its `index.html`.
+///
+/// Paths containing `..` are refused with 403, as are paths that lead out
+/// of the root through a symlink. Paths with a segment starting with `.`,
+/// such as `/.git/config`, are answered with 404.
+///
+/// Content-Type comes from the file's extension, or from its first bytes if
+/// the extension is missing or unknown.
+///
+/// Responses carry an `ETag` and `Last-Modified` from the file's metadata,
+/// and conditional requests get 304 Not Modified or 412 Precondition Failed
+/// as RFC 9110 describes. GET requests with a `Range` header get just the
+/// bytes asked for, as `multipart/byteranges` if there are several ranges.
+///
+/// If a file has a pre-compressed `.gz` sibling, such as `app.js.gz` next
+/// to `app.js`, clients that accept gzip get that instead.
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    mime_types: MimeTypes,
+    weak_etags: bool,
+    precompressed: bool,
+}
+
+impl StaticFiles {
+    /// Creates a handler serving files under root.
+    ///
+    /// Returns an error if root does not exist.
+    pub fn new(root: impl AsRef<Path>) -> io::Result<StaticFiles> {
+        let root = fs::canonicalize(root)?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(StaticFiles {
+            root,
+            mime_types: MimeTypes::new(),
+            weak_etags: false,
+            precompressed: true,
+        })
+    }
+
+    /// Sets the table used to pick Content-Type from file extensions.
+    pub fn mime_types(mut self, mime_types: MimeTypes) -> StaticFiles {
+        self.mime_types = mime_types;
+        self
+    }
+
+    /// Sets whether ETags are weak (`W/"..."`). They are strong by default,
+    /// which promises the same tag means the same bytes; use weak tags if
+    /// files can change without their size or modification time changing.
+    pub fn weak_etags(mut self, weak: bool) -> StaticFiles {
+        self.weak_etags = weak;
+        self
+    }
+
+    /// Sets whether `.gz` siblings are served to clients that accept gzip.
+    /// Defaults to true.
+    pub fn precompressed(mut self, precompressed: bool) -> StaticFiles {
+        self.precompressed = precompressed;
+        self
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn serve(&self, request: &Request) -> Result<Response, StatusCode> {
+        // Router parameters are already percent-decoded.
+        let decoded = match request.param("path") {
+            Some(path) => path.to_string(),
+            None => percent_decode(request.path()).ok_or(StatusCode::BadRequest)?,
+        };
+
+        let mut path = self.root.clone();
+        for segment in decoded.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return Err(StatusCode::Forbidden),
+                // Hidden files such as .git or .env are never served.
+                _ if segment.starts_with('.') => return Err(StatusCode::NotFound),
+                _ if segment.contains('\\') || segment.contains('\0') => {
+                    return Err(StatusCode::BadRequest)
+                }
+                _ => path.push(segment),
+            }
+        }
+
+        let mut path = fs::canonicalize(&path).map_err(status_for)?;
+        if !path.starts_with(&self.root) {
+            return Err(StatusCode::Forbidden);
+        }
+        if path.is_dir() {
+            // Relative links in the index only work if the URL ends in '/'.
+            if !request.path().ends_with('/') {
+                let location = match request.query() {
+                    Some(query) => format!("{}/?{}", request.path(), query),
+                    None => format!("{}/", request.path()),
+                };
+                return Ok(
+                    Response::new(StatusCode::MovedPermanently).with_header("Location", location)
+                );
+            }
+            path.push("index.html");
+        }
+
+        let mut file = File::open(&path).map_err(status_for)?;
+        let mut metadata = file.metadata().map_err(status_for)?;
+        if !metadata.is_file() {
+            return Err(StatusCode::NotFound);
+        }
+        let content_type = match self.mime_types.lookup(&path) {
+            Some(content_type) => content_type,
+            None => sniff(&mut file).map_err(status_for)?,
+        };
+
+        let mut response = Response::new(StatusCode::Ok).with_header("Accept-Ranges", "bytes");
+        if let Some((gz_file, gz_metadata)) = self.gzip_sibling(&path) {
+            add_vary(response.headers_mut());
+            if negotiate(request.headers(), &["gzip"]).is_some() {
+                file = gz_file;
+                metadata = gz_metadata;
+                response.headers_mut().insert("Content-Encoding", "gzip");
+            }
+        }
+
+        let validators =
+            Validators::for_file(metadata.len(), metadata.modified().ok(), self.weak_etags);
+        if let Some(etag) = &validators.etag {
+            response.headers_mut().insert("ETag", etag.as_str());
+        }
+        if let Some(modified) = validators.last_modified {
+            response
+                .headers_mut()
+                .insert("Last-Modified", httpdate::format(modified));
+        }
+        match validators.check(request) {
+            Some(StatusCode::NotModified) => {
+                response.set_status(StatusCode::NotModified);
+                return Ok(response);
+            }
+            Some(status) => return Err(status),
+            None => {}
+        }
+
+        let len = metadata.len();
+        let ranges = match request.header("Range") {
+            Some(header) if *request.method() == Method::Get && validators.if_range(request) => {
+                range::parse(header, len)
+            }
+            _ => Ranges::Ignore,
+        };
+        match ranges {
+            Ranges::Ignore => Ok(response
+                .with_header("Content-Type", content_type)
+                .with_stream(file, Some(len))),
+            Ranges::Unsatisfiable => Ok(Response::new(StatusCode::RangeNotSatisfiable)
+                .with_header("Content-Range", format!("bytes */{}", len))),
+            Ranges::Satisfiable(ranges) if ranges.len() == 1 => {
+                let range = &ranges[0];
+                file.seek(SeekFrom::Start(range.start))
+                    .map_err(status_for)?;
+                response.set_status(StatusCode::PartialContent);
+                Ok(response
+                    .with_header("Content-Type", content_type)
+                    .with_header("Content-Range", range::content_range(range, len))
+                    .with_stream(
+                        file.take(range.end - range.start),
+                        Some(range.end - range.start),
+                    ))
+            }
+            Ranges::Satisfiable(ranges) => {
+                let (body, boundary, body_len) = Multipart::new(file, &ranges, &content_type, len);
+                response.set_status(StatusCode::PartialContent);
+                Ok(response
+                    .with_header(
+                        "Content-Type",
+                        format!("multipart/byteranges; boundary={}", boundary),
+                    )
+                    .with_stream(body, Some(body_len)))
+            }
+        }
+    }
+}
+
+impl StaticFiles {
+    /// Opens the `.gz` file next to path, if there is one inside the root.
+    fn gzip_sibling(&self, path: &Path) -> Option<(File, Metadata)> {
+        if !self.precompressed {
+            return None;
+        }
+        let mut name = path.file_name()?.to_os_string();
+        name.push(".gz");
+        let gz = fs::canonicalize(path.with_file_name(name)).ok()?;
+        if !gz.starts_with(&self.root) {
+            return None;
+        }
+        let file = File::open(&gz).ok()?;
+        let metadata = file.metadata().ok()?;
+        metadata.is_file().then_some((file, metadata))
+    }
+}
+
+/// Sniffs the Content-Type of file from its first bytes, leaving it at the
+/// start.
+fn sniff(file: &mut File) -> io::Result<String> {
+    let mut start = Vec::with_capacity(512);
+    file.by_ref().take(512).read_to_end(&mut start)?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(MimeTypes::sniff(&start))
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, request: &mut Request) -> Response {
+        if !matches!(request.method(), Method::Get | Method::Head) {
+            return Response::text(StatusCode::MethodNotAllowed, "Method Not Allowed")
+                .with_header("Allow", "GET, HEAD");
+        }
+        self.serve(request)
+            .unwrap_or_else(|status| Response::text(status, status.reason_phrase()))
+    }
+}
+
+fn status_for(e: io::Error) -> StatusCode {
+    match e.kind() {
+        io::ErrorKind::NotFound => StatusCode::NotFound,
+        io::ErrorKind::PermissionDenied => StatusCode::Forbidden,
+        _ => StatusCode::InternalServerError,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    fn get(files: &StaticFiles, path: &str) -> StatusCode {
+        let raw = format!("GET {} HTTP/1.1\r\nHost: a\r\n\r\n", path);
+        let mut request = Request::read_from(&mut raw.as_bytes()).unwrap();
+        files.handle(&mut request).status()
+    }
+
+    #[test]
+    fn refuses_hidden_and_escaping_paths() {
+        let root = env::temp_dir().join(format!("websvr-files-{}", process::id()));
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::create_dir_all(root.join("dir")).unwrap();
+        fs::write(root.join(".git/config"), "secret").unwrap();
+        fs::write(root.join(".env"), "secret").unwrap();
+        fs::write(root.join("dir/.hidden"), "secret").unwrap();
+        fs::write(root.join("dir/a.txt"), "a").unwrap();
+        let files = StaticFiles::new(&root).unwrap();
+
+        let statuses: Vec<_> = [
+            "/dir/a.txt",
+            "/dir/",
+            "/dir",
+            "/.git/config",
+            "/.env",
+            "/dir/.hidden",
+            "/%2egit/config",
+            "/dir/../.env",
+            "/missing",
+        ]
+        .iter()
+        .map(|path| get(&files, path))
+        .collect();
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(
+            statuses,
+            [
+                StatusCode::Ok,
+                StatusCode::NotFound,
+                StatusCode::MovedPermanently,
+                StatusCode::NotFound,
+                StatusCode::NotFound,
+                StatusCode::NotFound,
+                StatusCode::NotFound,
+                StatusCode::Forbidden,
+                StatusCode::NotFound,
+            ]
+        );
+    }
+}
@@ -0,0 +1,106 @@
+use crate::{Request, Response, Router};
+
+/// Something that answers requests.
+///
+/// Closures taking a `&Request` are handlers, as are Router and Chain.
+pub trait Handler: Send + Sync {
+    fn handle(&self, request: &mut Request) -> Response;
+}
+
+impl<F> Handler for F
+where
+    F: Fn(&Request) -> Response + Send + Sync,
+{
+    fn handle(&self, request: &mut Request) -> Response {
+        self(request)
+    }
+}
+
+impl Handler for Router {
+    fn handle(&self, request: &mut Request) -> Response {
+        Router::handle(self, request)
+    }
+}
+
+/// A layer around a Handler.
+///
+/// A middleware can inspect or change the request before passing it on with
+/// `next.run`, answer it itself without calling next, and rewrite the
+/// response it gets back.
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response;
+}
+
+impl<F> Middleware for F
+where
+    F: Fn(&mut Request, Next<'_>) -> Response + Send + Sync,
+{
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        self(request, next)
+    }
+}
+
+/// The rest of a Chain, passed to each Middleware.
+pub struct Next<'a> {
+    middleware: &'a [Box<dyn Middleware>],
+    handler: &'a dyn Handler,
+}
+
+impl Next<'_> {
+    /// Passes request to the next middleware, or to the handler if there
+    /// are none left.
+    pub fn run(self, request: &mut Request) -> Response {
+        match self.middleware.split_first() {
+            Some((first, rest)) => first.handle(
+                request,
+                Next {
+                    middleware: rest,
+                    handler: self.handler,
+                },
+            ),
+            None => self.handler.handle(request),
+        }
+    }
+}
+
+/// A Handler wrapped in middleware.
+///
+/// Middleware runs in the order it was added, so the first one added sees
+/// the request first and the response last.
+pub struct Chain {
+    middleware: Vec<Box<dyn Middleware>>,
+    handler: Box<dyn Handler>,
+}
+
+impl Chain {
+    pub fn new<H: Handler + 'static>(handler: H) -> Chain {
+        Chain {
+            middleware: Vec::new(),
+            handler: Box::new(handler),
+        }
+    }
+
+    pub fn with<M: Middleware + 'static>(mut self, middleware: M) -> Chain {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Adds a middleware closure. Unlike `with`, the closure's argument
+    /// types don't need to be written out.
+    pub fn with_fn<F>(self, middleware: F) -> Chain
+    where
+        F: Fn(&mut Request, Next<'_>) -> Response + Send + Sync + 'static,
+    {
+        self.with(middleware)
+    }
+}
+
+impl Handler for Chain {
+    fn handle(&self, request: &mut Request) -> Response {
+        Next {
+            middleware: &self.middleware,
+            handler: &*self.handler,
+        }
+        .run(request)
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    any::Any,
+    error::Error,
+    fmt,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+/// A handle to a job submitted with `ThreadPool::submit`.
+///
+/// The handle can be joined (blocking), polled, or waited on with a timeout.
+pub struct JobHandle<T> {
+    slot: Arc<Slot<T>>,
+}
+
+impl<T> JobHandle<T> {
+    /// Returns true once the job has finished, panicked or been cancelled.
+    pub fn is_finished(&self) -> bool {
+        self.slot.result.lock().unwrap().is_some()
+    }
+
+    /// Blocks until the job is done and returns its result.
+    pub fn join(self) -> Result<T, JobError> {
+        let result = self.slot.result.lock().unwrap();
+        let mut result = self.slot.done.wait_while(result, |r| r.is_none()).unwrap();
+        result.take().unwrap()
+    }
+
+    /// Returns the job's result if it is done, or gives the handle back if it is not.
+    pub fn try_join(self) -> Result<Result<T, JobError>, JobHandle<T>> {
+        let taken = self.slot.result.lock().unwrap().take();
+        taken.ok_or(self)
+    }
+
+    /// Blocks for at most timeout waiting for the job's result.
+    ///
+    /// The handle is given back if the job has not finished in time.
+    pub fn join_timeout(self, timeout: Duration) -> Result<Result<T, JobError>, JobHandle<T>> {
+        let result = self.slot.result.lock().unwrap();
+        let (mut result, _) = self
+            .slot
+            .done
+            .wait_timeout_while(result, timeout, |r| r.is_none())
+            .unwrap();
+        let taken = result.take();
+        drop(result);
+        taken.ok_or(self)
+    }
+}
+
+impl<T> fmt::Debug for JobHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobHandle")
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+/// The reason a submitted job produced no value.
+#[derive(Debug)]
+pub enum JobError {
+    /// The job panicked; the panic payload is kept.
+    Panicked(Box<dyn Any + Send + 'static>),
+    /// The job was dropped before it ran, e.g. because the pool shut down.
+    Cancelled,
+}
+
+impl JobError {
+    /// Returns true if the job panicked.
+    pub fn is_panic(&self) -> bool {
+        matches!(self, JobError::Panicked(_))
+    }
+
+    /// Returns the panic message if the job panicked with a string payload.
+    pub fn panic_message(&self) -> Option<&str> {
+        match self {
+            JobError::Panicked(payload) => panic_message(payload.as_ref()),
+            JobError::Cancelled => None,
+        }
+    }
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::Panicked(_) => match self.panic_message() {
+                Some(msg) => write!(f, "job panicked: {}", msg),
+                None => write!(f, "job panicked"),
+            },
+            JobError::Cancelled => write!(f, "job was cancelled before it ran"),
+        }
+    }
+}
+
+impl Error for JobError {}
+
+/// The sending half of a `JobHandle`, moved into the job itself.
+///
+/// Dropping it without completing marks the job as cancelled.
+pub(crate) struct Completion<T> {
+    slot: Option<Arc<Slot<T>>>,
+}
+
+impl<T> Completion<T> {
+    pub(crate) fn complete(mut self, result: Result<T, JobError>) {
+        if let Some(slot) = self.slot.take() {
+            slot.fill(result);
+        }
+    }
+}
+
+impl<T> Drop for Completion<T> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot.take() {
+            slot.fill(Err(JobError::Cancelled));
+        }
+    }
+}
+
+struct Slot<T> {
+    result: Mutex<Option<Result<T, JobError>>>,
+    done: Condvar,
+}
+
+impl<T> Slot<T> {
+    fn fill(&self, result: Result<T, JobError>) {
+        *self.result.lock().unwrap() = Some(result);
+        self.done.notify_all();
+    }
+}
+
+/// Creates a connected `Completion` and `JobHandle` pair.
+pub(crate) fn channel<T>() -> (Completion<T>, JobHandle<T>) {
+    let slot = Arc::new(Slot {
+        result: Mutex::new(None),
+        done: Condvar::new(),
+    });
+    (
+        Completion {
+            slot: Some(Arc::clone(&slot)),
+        },
+        JobHandle { slot },
+    )
+}
+
+/// Extracts the message from a panic payload, if it is a string.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    if let Some(msg) = payload.downcast_ref::<&'static str>() {
+        Some(msg)
+    } else {
+        payload.downcast_ref::<String>().map(String::as_str)
+    }
+}
+
+/// Describes a job that panicked on a worker, passed to the pool's panic handler.
+pub struct JobPanic<'a> {
+    worker: usize,
+    payload: &'a (dyn Any + Send),
+}
+
+impl<'a> JobPanic<'a> {
+    pub(crate) fn new(worker: usize, payload: &'a (dyn Any + Send)) -> JobPanic<'a> {
+        JobPanic { worker, payload }
+    }
+
+    /// The id of the worker the job was running on.
+    pub fn worker_id(&self) -> usize {
+        self.worker
+    }
+
+    /// The raw panic payload.
+    pub fn payload(&self) -> &(dyn Any + Send) {
+        self.payload
+    }
+
+    /// The panic message, if the payload is a string.
+    pub fn message(&self) -> Option<&str> {
+        panic_message(self.payload)
+    }
+}
+
+impl fmt::Debug for JobPanic<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("JobPanic")
+            .field("worker", &self.worker)
+            .field("message", &self.message())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{wait_until, Gate},
+        ShutdownMode, ThreadPool,
+    };
+
+    #[test]
+    fn submit_returns_the_result() {
+        let pool = ThreadPool::new(2);
+        let handles: Vec<_> = (0..10).map(|i| pool.submit(move || i * i)).collect();
+        let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, [0, 1, 4, 9, 16, 25, 36, 49, 64, 81]);
+    }
+
+    #[test]
+    fn handle_is_given_back_until_the_job_is_done() {
+        let pool = ThreadPool::new(1);
+        let gate = Gate::new();
+        let handle = pool.submit({
+            let gate = gate.clone();
+            move || {
+                gate.wait();
+                "done"
+            }
+        });
+
+        assert!(!handle.is_finished());
+        let handle = handle.try_join().unwrap_err();
+        let handle = handle.join_timeout(Duration::from_millis(20)).unwrap_err();
+        gate.open();
+        wait_until("the job finishes", || handle.is_finished());
+        assert_eq!(format!("{:?}", handle), "JobHandle { finished: true }");
+        assert_eq!(
+            handle.join_timeout(Duration::ZERO).unwrap().unwrap(),
+            "done"
+        );
+    }
+
+    #[test]
+    fn panics_are_reported_through_the_handle() {
+        let pool = ThreadPool::new(1);
+
+        let error = pool.submit(|| panic!("boom {}", 1)).join().unwrap_err();
+        assert!(error.is_panic());
+        assert_eq!(error.panic_message(), Some("boom 1"));
+        assert_eq!(error.to_string(), "job panicked: boom 1");
+
+        let error = pool
+            .submit(|| std::panic::panic_any(42))
+            .join()
+            .unwrap_err();
+        assert_eq!(*error_payload(&error).downcast_ref::<i32>().unwrap(), 42);
+        assert_eq!(error.panic_message(), None);
+        assert_eq!(error.to_string(), "job panicked");
+
+        // The worker carries on.
+        assert_eq!(pool.submit(|| 7).join().unwrap(), 7);
+    }
+
+    fn error_payload(error: &JobError) -> &(dyn Any + Send) {
+        match error {
+            JobError::Panicked(payload) => payload.as_ref(),
+            JobError::Cancelled => panic!("expected a panic"),
+        }
+    }
+
+    #[test]
+    fn discarded_jobs_are_cancelled() {
+        let pool = ThreadPool::new(1);
+        let gate = Gate::new();
+        let running = pool.submit({
+            let gate = gate.clone();
+            move || gate.wait()
+        });
+        wait_until("the first job runs", || pool.queued() == 0);
+        let queued = pool.submit(|| 1);
+
+        let shutdown = pool.shutdown(ShutdownMode::Cancel, Duration::from_millis(20));
+        assert!(shutdown.is_err());
+        gate.open();
+        assert!(running.join().is_ok());
+        let error = queued.join().unwrap_err();
+        assert!(matches!(error, JobError::Cancelled));
+        assert!(!error.is_panic());
+        assert_eq!(error.to_string(), "job was cancelled before it ran");
+    }
+}
@@ -1,11 +1,73 @@
 use std::{
-    sync::{mpsc, Arc, Mutex},
+    any::Any,
+    fmt, io,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Condvar, Mutex,
+    },
     thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
+mod builder;
+mod chunked;
+mod compression;
+mod conditional;
+mod config;
+mod connection;
+mod deflate;
+mod events;
+mod files;
+mod handler;
+mod headers;
+mod httpdate;
+mod job;
+mod mime;
+mod queue;
+mod range;
+mod request;
+mod response;
+mod router;
+mod scheduler;
+mod server;
+mod shutdown;
+mod stats;
+#[cfg(test)]
+mod testing;
+mod timer;
+
+pub use builder::ThreadPoolBuilder;
+pub use chunked::ChunkedWriter;
+pub use compression::Compression;
+pub use config::{Config, ConfigError, DocumentRoot, RouteAction, RouteConfig};
+pub use connection::{serve_connection, KeepAlive};
+pub use events::{EventListener, ExitReason, Level, PoolEvent, StderrLogger};
+pub use files::StaticFiles;
+pub use handler::{Chain, Handler, Middleware, Next};
+pub use headers::Headers;
+pub use job::{JobError, JobHandle, JobPanic};
+pub use mime::MimeTypes;
+pub use queue::{QueuePolicy, TryExecuteError};
+pub use request::{Limits, Method, ParseError, Request, Version};
+pub use response::{Body, BodyWriter, Response, StatusCode};
+pub use router::{Params, Router};
+pub use scheduler::Scheduler;
+pub use server::{Server, ServerError, ServerHandle};
+pub use shutdown::{ShutdownError, ShutdownMode};
+pub use stats::{Histogram, PoolStats};
+pub use timer::TimerHandle;
+
+use builder::{PoolSize, WorkerConfig};
+use queue::Capacity;
+use scheduler::JobQueue;
+use stats::Counters;
+use timer::Timers;
+
 pub struct ThreadPool {
-    workers: Vec<Worker>,
-    sender: Option<mpsc::Sender<Job>>,
+    shared: Arc<Shared>,
+    stopped: bool,
 }
 
 impl ThreadPool {
@@ -19,83 +81,664 @@ impl ThreadPool {
     pub fn new(n: usize) -> ThreadPool {
         assert!(n > 0);
 
-        let (sender, receiver) = mpsc::channel();
-        let receiver = Arc::new(Mutex::new(receiver));
+        ThreadPoolBuilder::new().num_threads(n).build().unwrap()
+    }
 
-        let mut workers = Vec::with_capacity(n);
+    /// Creates a new ThreadPool whose job queue holds at most capacity jobs.
+    ///
+    /// n is the number of threads in the pool. policy decides what `execute`
+    /// does when the queue is full.
+    ///
+    /// # Panics
+    ///
+    /// The `bounded` function will panic if n or capacity is zero.
+    pub fn bounded(n: usize, capacity: usize, policy: QueuePolicy) -> ThreadPool {
+        assert!(n > 0);
+        assert!(capacity > 0);
 
-        for id in 0..n {
-            workers.push(Worker::new(id, Arc::clone(&receiver)))
-        }
-        ThreadPool {
-            workers,
-            sender: Some(sender),
-        }
+        ThreadPoolBuilder::new()
+            .num_threads(n)
+            .queue_capacity(capacity)
+            .queue_policy(policy)
+            .build()
+            .unwrap()
     }
 
     /// Creates a new ThreadPool.
     ///
-    /// n is the number of threads in the pool.
+    /// n is the number of threads in the pool. Use `ThreadPoolBuilder` for
+    /// more options.
     pub fn build(n: usize) -> Result<ThreadPool, PoolCreationError> {
-        if n > 0 {
-            Ok(Self::new(n))
-        } else {
-            return Err(PoolCreationError(String::from("n must be greater than 0")));
+        ThreadPoolBuilder::new().num_threads(n).build()
+    }
+
+    fn spawn(
+        size: PoolSize,
+        capacity: Option<usize>,
+        policy: QueuePolicy,
+        scheduler: Scheduler,
+        config: WorkerConfig,
+        panic_handler: Option<PanicHandler>,
+        listener: Option<Arc<dyn EventListener>>,
+    ) -> io::Result<ThreadPool> {
+        let (startup, started) = mpsc::channel();
+        let shared = Arc::new(Shared {
+            queue: JobQueue::new(scheduler, size.max),
+            capacity: Capacity::new(capacity),
+            policy,
+            config,
+            workers: Mutex::new(Vec::with_capacity(size.max)),
+            live: AtomicUsize::new(0),
+            idle: AtomicUsize::new(0),
+            threads: Mutex::new(0),
+            exited: Condvar::new(),
+            cancelled: AtomicBool::new(false),
+            counters: Counters::default(),
+            timers: Timers::default(),
+            size,
+            panic_handler: Mutex::new(panic_handler),
+            listener,
+            startup: Mutex::new(Some(startup)),
+        });
+        let pool = ThreadPool {
+            shared,
+            stopped: false,
+        };
+
+        // If a spawn fails, dropping the pool shuts down the workers already started.
+        for _ in 0..pool.shared.size.min {
+            pool.shared.live.fetch_add(1, Ordering::SeqCst);
+            Worker::spawn_into(&pool.shared)?;
+        }
+        let failed = started
+            .iter()
+            .take(pool.shared.size.min)
+            .find_map(Result::err);
+        *pool.shared.startup.lock().unwrap() = None;
+        match failed {
+            Some(message) => Err(io::Error::other(message)),
+            None => Ok(pool),
         }
     }
 
     /// Batches a closure to be run by a worker in the ThreadPool
     ///
-    /// f is the closure to be run.
+    /// f is the closure to be run. If the queue is bounded and full, the
+    /// pool's `QueuePolicy` decides whether this blocks, drops f or runs f
+    /// on the calling thread.
     pub fn execute<F>(&self, f: F)
     where
         F: FnOnce() + Send + 'static,
     {
-        let job = Box::new(f);
-        self.sender.as_ref().unwrap().send(job).unwrap();
+        self.shared.execute(Box::new(f));
+    }
+
+    /// Batches a closure to be run by a worker in the ThreadPool without
+    /// blocking.
+    ///
+    /// f is the closure to be run. If the queue is full, f is handed back
+    /// inside the error, whatever the pool's `QueuePolicy`.
+    pub fn try_execute<F>(&self, f: F) -> Result<(), TryExecuteError<F>>
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        if !self.shared.capacity.try_reserve() {
+            return Err(TryExecuteError::new(f));
+        }
+        self.shared.send(Box::new(f));
+        Ok(())
+    }
+
+    /// Runs a closure on the ThreadPool once delay has passed.
+    ///
+    /// The returned handle can cancel it. Jobs still waiting for their
+    /// delay when the pool shuts down never run, nor do jobs whose delay is
+    /// too large to represent, such as `Duration::MAX`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the pool's timer thread cannot be spawned.
+    pub fn execute_after<F>(&self, delay: Duration, f: F) -> TimerHandle
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        Timers::once(&self.shared, delay, Box::new(f)).expect("failed to spawn timer thread")
+    }
+
+    /// Runs a closure on the ThreadPool every interval, starting one
+    /// interval from now, until the returned handle is cancelled or the pool
+    /// shuts down.
+    ///
+    /// A run is skipped if the previous one is still queued or running.
+    /// The job stops once the next run would be too far off to represent.
+    ///
+    /// # Panics
+    ///
+    /// Panics if interval is zero, or if the pool's timer thread cannot be
+    /// spawned.
+    pub fn execute_every<F>(&self, interval: Duration, f: F) -> TimerHandle
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        assert!(
+            !interval.is_zero(),
+            "execute_every needs a non-zero interval"
+        );
+        Timers::every(&self.shared, interval, Arc::new(f)).expect("failed to spawn timer thread")
+    }
+
+    /// Returns the number of jobs waiting for a worker.
+    pub fn queued(&self) -> usize {
+        self.shared.capacity.len()
+    }
+
+    /// Returns the number of worker threads currently running.
+    pub fn size(&self) -> usize {
+        self.shared.live.load(Ordering::SeqCst)
+    }
+
+    /// Returns the number of worker threads waiting for a job.
+    pub fn idle(&self) -> usize {
+        self.shared.idle.load(Ordering::SeqCst)
+    }
+
+    /// Shuts the ThreadPool down, waiting at most timeout for the workers.
+    ///
+    /// With `ShutdownMode::Drain` queued jobs still run; with
+    /// `ShutdownMode::Cancel` they are discarded and only running jobs are
+    /// waited for. If workers are still busy at the deadline, the remaining
+    /// queued jobs are discarded and the busy workers are reported.
+    pub fn shutdown(mut self, mode: ShutdownMode, timeout: Duration) -> Result<(), ShutdownError> {
+        self.stop(mode, Some(Instant::now() + timeout))
+    }
+
+    fn stop(&mut self, mode: ShutdownMode, deadline: Option<Instant>) -> Result<(), ShutdownError> {
+        if self.stopped {
+            return Ok(());
+        }
+        self.stopped = true;
+        self.shared.emit(PoolEvent::ShutdownStarted { mode });
+
+        // Stop the timers first so nothing is queued after the queue closes.
+        self.shared.timers.stop();
+        self.shared.queue.close();
+        let mut discarded = 0;
+        if mode == ShutdownMode::Cancel {
+            discarded = self.shared.cancel();
+        }
+
+        if !self.shared.wait_for_threads(deadline) {
+            let busy = self.shared.busy_workers();
+            discarded += self.shared.cancel();
+            self.shared
+                .emit(PoolEvent::ShutdownFinished { busy: &busy });
+            return Err(ShutdownError::new(busy, discarded));
+        }
+
+        // A worker that dies may respawn its replacement while we are joining,
+        // so keep going until no thread handles are left. Workers report their
+        // own exit, panicked or not, so the join result adds nothing.
+        while let Some((_, thread)) = self.shared.take_thread() {
+            let _ = thread.join();
+        }
+        self.shared.emit(PoolEvent::ShutdownFinished { busy: &[] });
+        Ok(())
+    }
+
+    /// Returns a snapshot of the pool's queue, workers and job counters.
+    pub fn stats(&self) -> PoolStats {
+        let counters = &self.shared.counters;
+        PoolStats {
+            queued: self.queued(),
+            workers: self.size(),
+            busy: self.shared.busy_workers().len(),
+            idle: self.idle(),
+            completed: counters.completed(),
+            panicked: counters.panicked(),
+            queue_wait: counters.queue_wait(),
+            run_time: counters.run_time(),
+        }
+    }
+
+    /// Batches a closure to be run by a worker in the ThreadPool and returns
+    /// a handle to its result.
+    ///
+    /// f is the closure to be run. A panic inside f is caught and reported
+    /// through the returned JobHandle.
+    pub fn submit<F, T>(&self, f: F) -> JobHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let (completion, handle) = job::channel();
+        let shared = Arc::clone(&self.shared);
+        self.execute(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f)).map_err(JobError::Panicked);
+            if result.is_err() {
+                shared.counters.job_panicked();
+            }
+            completion.complete(result);
+        });
+        handle
+    }
+
+    /// Sets the closure called whenever a job run with `execute` panics.
+    ///
+    /// The panic is caught, so the worker carries on with the next job.
+    pub fn set_panic_handler<F>(&self, f: F)
+    where
+        F: Fn(&JobPanic<'_>) + Send + Sync + 'static,
+    {
+        *self.shared.panic_handler.lock().unwrap() = Some(Arc::new(f));
     }
 }
 
 impl Drop for ThreadPool {
     fn drop(&mut self) {
-        drop(self.sender.take());
+        // Without a deadline this waits for every worker, so it cannot fail.
+        let _ = self.stop(ShutdownMode::Drain, None);
+    }
+}
+
+#[derive(Debug)]
+pub struct PoolCreationError(String);
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to create thread pool: {}", self.0)
+    }
+}
+
+impl std::error::Error for PoolCreationError {}
+
+/// State shared between the ThreadPool and its workers.
+struct Shared {
+    queue: JobQueue,
+    capacity: Capacity,
+    policy: QueuePolicy,
+    config: WorkerConfig,
+    workers: Mutex<Vec<Worker>>,
+    size: PoolSize,
+    live: AtomicUsize,
+    idle: AtomicUsize,
+    threads: Mutex<usize>,
+    exited: Condvar,
+    cancelled: AtomicBool,
+    counters: Counters,
+    timers: Timers,
+    panic_handler: Mutex<Option<PanicHandler>>,
+    listener: Option<Arc<dyn EventListener>>,
+    /// While the pool is being built, where workers report whether their
+    /// on_start hook ran.
+    startup: Mutex<Option<mpsc::Sender<Result<(), String>>>>,
+}
+
+impl Shared {
+    fn take_thread(&self) -> Option<(usize, JoinHandle<()>)> {
+        let mut workers = self.workers.lock().unwrap();
+        workers
+            .iter_mut()
+            .find_map(|worker| worker.thread.take().map(|thread| (worker.id, thread)))
+    }
+
+    fn execute(self: &Arc<Self>, job: Job) {
+        match self.policy {
+            QueuePolicy::Block => self.capacity.reserve(),
+            QueuePolicy::Reject => {
+                if !self.capacity.try_reserve() {
+                    self.emit(PoolEvent::JobRejected);
+                    return;
+                }
+            }
+            QueuePolicy::CallerRuns => {
+                if !self.capacity.try_reserve() {
+                    job();
+                    return;
+                }
+            }
+        }
+        self.send(job);
+    }
+
+    fn send(self: &Arc<Self>, job: Job) {
+        self.queue.push(Task {
+            job,
+            queued_at: Instant::now(),
+        });
+        // More queued jobs than idle workers means some job would have to wait.
+        if self.capacity.len() > self.idle.load(Ordering::SeqCst) {
+            self.grow();
+        }
+    }
 
-        for worker in &mut self.workers {
-            println!("Shutting Down Worker {}...", worker.id);
-            if let Some(worker) = worker.thread.take() {
-                worker.join().unwrap();
+    /// Discards every queued job and any job a worker picks up from now on.
+    ///
+    /// Returns how many queued jobs were discarded.
+    fn cancel(&self) -> usize {
+        self.cancelled.store(true, Ordering::SeqCst);
+        let jobs = self.queue.drain();
+        for _ in &jobs {
+            self.capacity.release();
+        }
+        jobs.len()
+    }
+
+    fn busy_workers(&self) -> Vec<usize> {
+        let workers = self.workers.lock().unwrap();
+        workers
+            .iter()
+            .filter(|w| w.busy.load(Ordering::SeqCst))
+            .map(|w| w.id)
+            .collect()
+    }
+
+    /// Waits until every worker thread has exited, or the deadline passes.
+    ///
+    /// Returns false if the deadline passed first.
+    fn wait_for_threads(&self, deadline: Option<Instant>) -> bool {
+        let mut threads = self.threads.lock().unwrap();
+        while *threads > 0 {
+            match deadline {
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    threads = self.exited.wait_timeout(threads, deadline - now).unwrap().0;
+                }
+                None => threads = self.exited.wait(threads).unwrap(),
             }
         }
+        true
     }
-}
 
-pub struct PoolCreationError(String);
+    fn thread_exited(&self) {
+        let mut threads = self.threads.lock().unwrap();
+        *threads -= 1;
+        if *threads == 0 {
+            self.exited.notify_all();
+        }
+    }
+
+    /// Adds a worker if the pool is below its maximum size.
+    fn grow(self: &Arc<Self>) {
+        let grew = self
+            .live
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+                (live < self.size.max).then_some(live + 1)
+            })
+            .is_ok();
+        if grew {
+            if let Err(error) = Worker::spawn_into(self) {
+                self.emit(PoolEvent::WorkerSpawnFailed { error: &error });
+            }
+        }
+    }
+
+    /// Removes a worker whose on_start hook panicked. It is not replaced,
+    /// since its replacement would most likely panic too.
+    fn start_failed(&self, id: usize, message: String) {
+        self.workers.lock().unwrap().retain(|w| w.id != id);
+        self.live.fetch_sub(1, Ordering::SeqCst);
+        let error = io::Error::other(message);
+        self.emit(PoolEvent::WorkerSpawnFailed { error: &error });
+    }
+
+    /// Removes an idle worker if the pool is above its minimum size.
+    fn retire(&self, id: usize) -> bool {
+        let retired = self
+            .live
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |live| {
+                (live > self.size.min).then_some(live - 1)
+            })
+            .is_ok();
+        if retired {
+            self.workers.lock().unwrap().retain(|w| w.id != id);
+        }
+        retired
+    }
+
+    fn report_panic(&self, id: usize, payload: &(dyn Any + Send)) {
+        self.counters.job_panicked();
+        let handler = self.panic_handler.lock().unwrap().clone();
+        if let Some(handler) = handler {
+            handler(&JobPanic::new(id, payload));
+        }
+    }
+
+    fn emit(&self, event: PoolEvent<'_>) {
+        if let Some(listener) = &self.listener {
+            listener.on_event(&event);
+        }
+    }
+}
 
 struct Worker {
     id: usize,
     thread: Option<JoinHandle<()>>,
+    busy: Arc<AtomicBool>,
 }
 
 impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Worker {
-        let thread = thread::spawn(move || loop {
-            let job = receiver.lock().unwrap().recv();
-            match job {
-                Ok(job) => {
-                    println!("Worker {} received a job! Executing...", id);
-                    job();
-                    println!("Worker {} Finished!", id);
+    /// Spawns a worker under the lowest unused id and registers it.
+    ///
+    /// The caller must already have counted it in `live`.
+    fn spawn_into(shared: &Arc<Shared>) -> io::Result<()> {
+        let mut workers = shared.workers.lock().unwrap();
+        let id = (0..)
+            .find(|id| workers.iter().all(|w| w.id != *id))
+            .unwrap();
+        match Worker::new(id, Arc::clone(shared)) {
+            Ok(worker) => {
+                workers.push(worker);
+                Ok(())
+            }
+            Err(e) => {
+                shared.live.fetch_sub(1, Ordering::SeqCst);
+                Err(e)
+            }
+        }
+    }
+
+    fn new(id: usize, shared: Arc<Shared>) -> io::Result<Worker> {
+        let busy = Arc::new(AtomicBool::new(false));
+        *shared.threads.lock().unwrap() += 1;
+        let spawned = shared.config.spawn(id, {
+            let shared = Arc::clone(&shared);
+            let busy = Arc::clone(&busy);
+            move || worker_loop(id, shared, busy)
+        });
+        match spawned {
+            Ok(thread) => Ok(Worker {
+                id,
+                thread: Some(thread),
+                busy,
+            }),
+            Err(e) => {
+                shared.thread_exited();
+                Err(e)
+            }
+        }
+    }
+}
+
+fn worker_loop(id: usize, shared: Arc<Shared>, busy: Arc<AtomicBool>) {
+    let _sentinel = Sentinel {
+        id,
+        shared: Arc::clone(&shared),
+    };
+    shared.queue.register(id);
+    let started = shared.config.started(id);
+    if let Some(startup) = &*shared.startup.lock().unwrap() {
+        let _ = startup.send(started.clone());
+    }
+    if let Err(message) = started {
+        return shared.start_failed(id, message);
+    }
+    shared.emit(PoolEvent::WorkerStarted { worker: id });
+    let reason = loop {
+        shared.idle.fetch_add(1, Ordering::SeqCst);
+        let keep_alive = shared.size.is_elastic().then_some(shared.size.keep_alive);
+        let job = shared.queue.pop(id, keep_alive);
+        shared.idle.fetch_sub(1, Ordering::SeqCst);
+        match job {
+            Ok(task) => {
+                shared.capacity.release();
+                if shared.cancelled.load(Ordering::SeqCst) {
+                    continue;
                 }
-                Err(_) => {
-                    println!("Worker {} Shutting Down", id);
-                    break;
+                let queue_wait = task.queued_at.elapsed();
+                shared.counters.job_started(queue_wait);
+                shared.emit(PoolEvent::JobStarted {
+                    worker: id,
+                    queue_wait,
+                });
+                busy.store(true, Ordering::SeqCst);
+                let started = Instant::now();
+                let result = panic::catch_unwind(AssertUnwindSafe(task.job));
+                busy.store(false, Ordering::SeqCst);
+                let run_time = started.elapsed();
+                shared.counters.job_finished(run_time);
+                shared.emit(PoolEvent::JobFinished {
+                    worker: id,
+                    run_time,
+                    panicked: result.is_err(),
+                });
+                if let Err(payload) = result {
+                    shared.report_panic(id, payload.as_ref());
                 }
-            };
-        });
-        Worker {
-            id,
-            thread: Some(thread),
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if shared.retire(id) {
+                    break ExitReason::Retired;
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break ExitReason::Shutdown,
+        };
+    };
+    shared.emit(PoolEvent::WorkerExited { worker: id, reason });
+    shared.config.stopped(id);
+}
+
+/// Lives on a worker's stack and spawns a replacement if the thread unwinds,
+/// so the pool keeps its configured size.
+struct Sentinel {
+    id: usize,
+    shared: Arc<Shared>,
+}
+
+impl Sentinel {
+    fn respawn(&self) {
+        let replacement = Worker::new(self.id, Arc::clone(&self.shared));
+        let mut workers = self.shared.workers.lock().unwrap();
+        match replacement {
+            Ok(replacement) => {
+                if let Some(worker) = workers.iter_mut().find(|w| w.id == self.id) {
+                    *worker = replacement;
+                }
+            }
+            Err(error) => {
+                self.shared
+                    .emit(PoolEvent::WorkerSpawnFailed { error: &error });
+                workers.retain(|w| w.id != self.id);
+                self.shared.live.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+}
+
+impl Drop for Sentinel {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.shared.emit(PoolEvent::WorkerExited {
+                worker: self.id,
+                reason: ExitReason::Panicked,
+            });
+            if !self.shared.cancelled.load(Ordering::SeqCst) {
+                self.respawn();
+            }
         }
+        self.shared.thread_exited();
     }
 }
+
 type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// A job waiting in the queue, stamped with when it was sent.
+struct Task {
+    job: Job,
+    queued_at: Instant,
+}
+type PanicHandler = Arc<dyn Fn(&JobPanic<'_>) + Send + Sync + 'static>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::wait_until;
+
+    #[test]
+    fn panicking_jobs_leave_the_workers_running() {
+        let pool = ThreadPool::new(2);
+        let panics = Arc::new(Mutex::new(Vec::new()));
+        pool.set_panic_handler({
+            let panics = Arc::clone(&panics);
+            move |panic| {
+                assert!(panic.worker_id() < 2);
+                panics
+                    .lock()
+                    .unwrap()
+                    .push(panic.message().map(str::to_string));
+            }
+        });
+
+        for i in 0..10 {
+            pool.execute(move || panic!("job {}", i));
+        }
+        pool.execute(|| std::panic::panic_any(()));
+        wait_until("every panic is reported", || {
+            panics.lock().unwrap().len() == 11
+        });
+        let mut messages = panics.lock().unwrap().clone();
+        messages.sort();
+        assert_eq!(messages[0], None);
+        assert_eq!(messages[1].as_deref(), Some("job 0"));
+
+        assert_eq!(pool.size(), 2);
+        assert_eq!(pool.submit(|| 1).join().unwrap(), 1);
+    }
+
+    #[test]
+    fn dead_workers_are_replaced() {
+        let exits = Arc::new(Mutex::new(Vec::new()));
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(2)
+            .panic_handler(|_| panic!("the panic handler panicked"))
+            .event_listener({
+                let exits = Arc::clone(&exits);
+                move |event: &PoolEvent<'_>| {
+                    if let PoolEvent::WorkerExited { worker, reason } = event {
+                        exits.lock().unwrap().push((*worker, *reason));
+                    }
+                }
+            })
+            .build()
+            .unwrap();
+
+        // A panic outside of a job, here in the panic handler, kills the
+        // worker thread.
+        for _ in 0..3 {
+            pool.execute(|| panic!("job"));
+        }
+        wait_until("three workers die", || exits.lock().unwrap().len() == 3);
+        assert!(exits
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|&(worker, reason)| worker < 2 && reason == ExitReason::Panicked));
+
+        assert_eq!(pool.size(), 2);
+        let results: Vec<_> = (0..4).map(|i| pool.submit(move || i)).collect();
+        let results: Vec<_> = results.into_iter().map(|h| h.join().unwrap()).collect();
+        assert_eq!(results, [0, 1, 2, 3]);
+    }
+}
@@ -1,44 +1,407 @@
 use std::{
-    fs,
-    io::{BufRead, BufReader, Write},
-    net::{TcpListener, TcpStream},
+    env, fs,
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime},
 };
 
-use websvr::ThreadPool;
+use websvr::{
+    Chain, Compression, Config, DocumentRoot, Level, Method, MimeTypes, QueuePolicy, Response,
+    RouteAction, RouteConfig, Router, Server, ServerHandle, StaticFiles, StatusCode, StderrLogger,
+    ThreadPoolBuilder,
+};
+
+/// How often the config file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(1);
+
+const USAGE: &str = "\
+Usage: websvr [OPTIONS]
+
+Options:
+  --config <FILE>   Read listeners, roots, routes, error pages, media types
+                    and limits from FILE [env: WEBSVR_CONFIG]
+  --bind <ADDR>     Address to listen on [env: WEBSVR_BIND] [default: 127.0.0.1]
+  --port <PORT>     Port to listen on [env: WEBSVR_PORT] [default: 7878]
+  --workers <N>     Worker threads kept running; up to four times as many are
+                    started under load [env: WEBSVR_WORKERS] [default: 4]
+  --root <DIR>      Directory to serve files from [env: WEBSVR_ROOT]
+  -h, --help        Print this help
+
+Without --config or --root, only hello.html at / and the 404.html error
+page are served, from the current directory.
+
+Options given alongside --config override the file: --bind and --port
+replace its listeners, and --root replaces its root for /. The file is
+read again when it changes; new listeners and worker counts take effect
+on restart.
+";
+
+/// Command line settings. Each falls back to an environment variable.
+struct Args {
+    config: Option<PathBuf>,
+    bind: Option<String>,
+    port: Option<u16>,
+    workers: Option<usize>,
+    root: Option<PathBuf>,
+}
+
+impl Args {
+    fn parse() -> Result<Args, String> {
+        let mut config = None;
+        let mut bind = None;
+        let mut port = None;
+        let mut workers = None;
+        let mut root = None;
+
+        let mut args = env::args().skip(1);
+        while let Some(arg) = args.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) => (name.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let slot = match name.as_str() {
+                "-h" | "--help" => {
+                    print!("{}", USAGE);
+                    process::exit(0);
+                }
+                "--config" => &mut config,
+                "--bind" => &mut bind,
+                "--port" => &mut port,
+                "--workers" => &mut workers,
+                "--root" => &mut root,
+                _ => return Err(format!("unknown option '{}'", name)),
+            };
+            let value = match inline.or_else(|| args.next()) {
+                Some(value) => value,
+                None => return Err(format!("{} needs a value", name)),
+            };
+            *slot = Some(value);
+        }
+
+        let port = match setting(port, "WEBSVR_PORT") {
+            Some(port) => Some(
+                port.parse()
+                    .map_err(|_| format!("invalid port '{}'", port))?,
+            ),
+            None => None,
+        };
+        let workers = match setting(workers, "WEBSVR_WORKERS") {
+            Some(workers) => match workers.parse() {
+                Ok(n) if n > 0 => Some(n),
+                _ => return Err(format!("invalid worker count '{}'", workers)),
+            },
+            None => None,
+        };
+        Ok(Args {
+            config: setting(config, "WEBSVR_CONFIG").map(PathBuf::from),
+            bind: setting(bind, "WEBSVR_BIND"),
+            port,
+            workers,
+            root: setting(root, "WEBSVR_ROOT").map(PathBuf::from),
+        })
+    }
+
+    /// Reads the config file, or makes the built-in config if there is
+    /// none, and applies the other settings on top.
+    fn config(&self) -> Result<Config, String> {
+        let mut config = match &self.config {
+            Some(path) => {
+                let mut config =
+                    Config::load(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+                if let Some(root) = &self.root {
+                    config.roots.retain(|r| r.prefix != "/");
+                    config.roots.push(document_root(root));
+                }
+                config
+            }
+            None => builtin_config(self.root.as_deref()),
+        };
+        if let Some(workers) = self.workers {
+            config.workers = workers;
+        }
+        if self.bind.is_some() || self.port.is_some() {
+            let host = self.bind.as_deref().unwrap_or("127.0.0.1");
+            let port = self.port.unwrap_or(7878);
+            // IPv6 addresses need brackets to be told apart from the port.
+            let addr = if host.contains(':') {
+                format!("[{}]:{}", host, port)
+            } else {
+                format!("{}:{}", host, port)
+            };
+            config.listeners = vec![addr];
+        }
+        Ok(config)
+    }
+}
+
+/// Returns the flag's value if it was given, else the environment
+/// variable's if it is set and not empty.
+fn setting(flag: Option<String>, var: &str) -> Option<String> {
+    flag.or_else(|| env::var(var).ok().filter(|v| !v.is_empty()))
+}
+
+/// The config used without a config file: `hello.html` as the home page
+/// and `404.html` as the 404 page. The rest of root is only served if it
+/// was given; by default nothing else in the current directory is.
+fn builtin_config(root: Option<&Path>) -> Config {
+    let dir = root.unwrap_or(Path::new("."));
+    Config {
+        roots: root.map(document_root).into_iter().collect(),
+        routes: vec![RouteConfig {
+            method: Method::Get,
+            path: "/".to_string(),
+            action: RouteAction::File(dir.join("hello.html")),
+        }],
+        error_pages: vec![(StatusCode::NotFound, dir.join("404.html"))],
+        ..Config::default()
+    }
+}
+
+fn document_root(dir: &Path) -> DocumentRoot {
+    DocumentRoot {
+        prefix: "/".to_string(),
+        dir: dir.to_path_buf(),
+        precompressed: true,
+        weak_etags: false,
+    }
+}
 
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
-    let pool = ThreadPool::new(4);
+    let args = Args::parse().unwrap_or_else(|e| {
+        eprintln!("websvr: {}\nTry 'websvr --help' for more information.", e);
+        process::exit(2)
+    });
+    let stamp = args.config.as_deref().and_then(file_stamp);
+    let config = args.config().unwrap_or_else(|e| fail(e));
+    // The demo page that shows off the pool, kept when there's no config.
+    let sleep_page = match args.config {
+        Some(_) => None,
+        None => Some(
+            args.root
+                .as_deref()
+                .unwrap_or(Path::new("."))
+                .join("hello.html"),
+        ),
+    };
 
-    for stream in listener.incoming() {
-        let stream = stream.unwrap();
+    let pool = ThreadPoolBuilder::new()
+        .min_threads(config.workers)
+        .max_threads(config.workers * 4)
+        .keep_alive(Duration::from_secs(30))
+        .thread_name("websvr-worker")
+        .queue_capacity(64)
+        .queue_policy(QueuePolicy::Reject)
+        .event_listener(StderrLogger::new(Level::Warn))
+        .build()
+        .unwrap_or_else(|e| fail(e));
+    let app = app(&config, sleep_page).unwrap_or_else(|e| fail(e));
 
-        pool.execute(|| handle_connection(stream));
+    let (first, rest) = config
+        .listeners
+        .split_first()
+        .unwrap_or_else(|| fail("no listeners"));
+    let mut server = Server::bind(first.as_str(), pool, app)
+        .unwrap_or_else(|e| fail(format!("{}: {}", first, e)));
+    for addr in rest {
+        server = server
+            .listen(addr.as_str())
+            .unwrap_or_else(|e| fail(format!("{}: {}", addr, e)));
     }
+    let server = server
+        .limits(config.limits)
+        .keep_alive(config.keep_alive.clone());
+    if args.config.is_some() {
+        let reloader = Reloader {
+            handle: server.handle(),
+            args,
+            started: config,
+            stamp: Mutex::new(stamp),
+        };
+        server
+            .pool()
+            .execute_every(RELOAD_INTERVAL, move || reloader.poll());
+    }
+    server.run()
+}
+
+/// Swaps in a new handler and settings when the config file changes.
+///
+/// Connections open at the time finish on the old handler. A config that
+/// fails to load is reported and the old one kept.
+struct Reloader {
+    args: Args,
+    handle: ServerHandle,
+    /// The config the server started with, for the settings that can't
+    /// change without a restart.
+    started: Config,
+    /// The file's stamp when it was last read.
+    stamp: Mutex<Option<FileStamp>>,
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&mut stream);
-    let request_line = buf_reader.lines().next().unwrap().unwrap();
+impl Reloader {
+    fn poll(&self) {
+        let path = self.args.config.as_deref().expect("reloading needs a file");
+        let stamp = match file_stamp(path) {
+            Some(stamp) => stamp,
+            // Probably mid-save; look again next time.
+            None => return,
+        };
+        let mut last = self.stamp.lock().unwrap();
+        if *last == Some(stamp) {
+            return;
+        }
+        *last = Some(stamp);
+
+        let config = match self.args.config() {
+            Ok(config) => config,
+            Err(e) => return reload_failed(e),
+        };
+        let app = match app(&config, None) {
+            Ok(app) => app,
+            Err(e) => return reload_failed(e),
+        };
+        if config.listeners != self.started.listeners || config.workers != self.started.workers {
+            eprintln!(
+                "[{:<5}] listener and worker changes in {} take effect on restart",
+                Level::Warn,
+                path.display()
+            );
+        }
+        self.handle.replace(app, config.limits, config.keep_alive);
+        eprintln!("[{:<5}] reloaded {}", Level::Info, path.display());
+    }
+}
+
+fn reload_failed(e: String) {
+    eprintln!("[{:<5}] kept the old config: {}", Level::Warn, e);
+}
+
+/// A file's modification time and length, to tell when it changes.
+type FileStamp = (Option<SystemTime>, u64);
+
+fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok(), metadata.len()))
+}
 
-    let (filename, status_line) = match &request_line[..] {
-        "GET / HTTP/1.1" => ("hello.html", "HTTP/1.1 200 OK"),
-        "GET /sleep HTTP/1.1" => {
+/// Builds the handler for config: its routes, then its roots, with the
+/// access log, compression and error pages around them as configured.
+fn app(config: &Config, sleep_page: Option<PathBuf>) -> Result<Chain, String> {
+    let mime_types = Arc::new(config.mime_types.clone());
+    let mut router = Router::new();
+    for route in &config.routes {
+        router = match &route.action {
+            RouteAction::File(path) => {
+                let path = path.clone();
+                let mime_types = Arc::clone(&mime_types);
+                router.route(route.method.clone(), &route.path, move |_| {
+                    page(StatusCode::Ok, &path, &mime_types)
+                })
+            }
+            RouteAction::Status { status, location } => {
+                let (status, location) = (*status, location.clone());
+                router.route(route.method.clone(), &route.path, move |_| {
+                    let mut response = Response::new(status);
+                    if !status.forbids_body() {
+                        response.set_body(status.reason_phrase());
+                        response
+                            .headers_mut()
+                            .insert("Content-Type", "text/plain; charset=utf-8");
+                    }
+                    if let Some(location) = &location {
+                        response.headers_mut().insert("Location", location.as_str());
+                    }
+                    response
+                })
+            }
+        };
+    }
+    if let Some(path) = sleep_page {
+        let mime_types = Arc::clone(&mime_types);
+        router = router.get("/sleep", move |_| {
             thread::sleep(Duration::from_secs(5));
             thread::sleep(Duration::from_secs(5));
-            ("hello.html", "HTTP/1.1 200 OK")
+            page(StatusCode::Ok, &path, &mime_types)
+        });
+    }
+    for root in &config.roots {
+        let files = StaticFiles::new(&root.dir)
+            .map_err(|e| format!("{}: {}", root.dir.display(), e))?
+            .mime_types(config.mime_types.clone())
+            .precompressed(root.precompressed)
+            .weak_etags(root.weak_etags);
+        router = if root.prefix == "/" {
+            router.fallback_handler(files)
+        } else {
+            router.route_handler(Method::Get, &format!("{}/*path", root.prefix), files)
+        };
+    }
+
+    let mut app = Chain::new(router);
+    if config.access_log {
+        app = app.with_fn(|request, next| {
+            let start = Instant::now();
+            let line = format!("{} {}", request.method(), request.target());
+            let response = next.run(request);
+            eprintln!(
+                "[{:<5}] {} -> {} in {:?}",
+                Level::Info,
+                line,
+                response.status(),
+                start.elapsed()
+            );
+            response
+        });
+    }
+    if config.compression {
+        app = app.with(Compression::new());
+    }
+    let error_pages = config.error_pages.clone();
+    Ok(app.with_fn(move |request, next| {
+        let mut response = next.run(request);
+        let status = response.status();
+        if let Some((_, path)) = error_pages.iter().find(|(s, _)| *s == status) {
+            // Without its page, the plain error will do.
+            if let Ok(contents) = fs::read(path) {
+                response
+                    .headers_mut()
+                    .insert("Content-Type", content_type(&mime_types, path, &contents));
+                response.set_body(contents);
+            }
         }
-        _ => ("404.html", "HTTP/1.1 404 NOT FOUND"),
-    };
+        response
+    }))
+}
+
+fn fail(e: impl std::fmt::Display) -> ! {
+    eprintln!("websvr: {}", e);
+    process::exit(1)
+}
 
-    let contents = fs::read_to_string(filename).unwrap();
-    let length = contents.len();
+/// Serves the file at path with status, or a 500 if it can't be read.
+fn page(status: StatusCode, path: &Path, mime_types: &MimeTypes) -> Response {
+    match fs::read(path) {
+        Ok(contents) => Response::new(status)
+            .with_header("Content-Type", content_type(mime_types, path, &contents))
+            .with_body(contents),
+        Err(e) => {
+            eprintln!(
+                "[{:<5}] failed to read {}: {}",
+                Level::Warn,
+                path.display(),
+                e
+            );
+            Response::text(StatusCode::InternalServerError, "Internal Server Error")
+        }
+    }
+}
 
-    let response = format!(
-        "{}\r\nContent Length: {}\r\n\r\n{}",
-        status_line, length, contents
-    );
-    stream.write_all(response.as_bytes()).unwrap();
+/// Picks the Content-Type for the file at path from its extension, or
+/// from its contents if the extension is unknown, as StaticFiles does.
+fn content_type(mime_types: &MimeTypes, path: &Path, contents: &[u8]) -> String {
+    mime_types
+        .lookup(path)
+        .unwrap_or_else(|| MimeTypes::sniff(contents))
 }
@@ -0,0 +1,164 @@
+use std::{collections::HashMap, path::Path};
+
+const BUILTIN: &[(&str, &str)] = &[
+    ("html", "text/html"),
+    ("htm", "text/html"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+    ("mjs", "text/javascript"),
+    ("txt", "text/plain"),
+    ("md", "text/markdown"),
+    ("csv", "text/csv"),
+    ("xml", "text/xml"),
+    ("json", "application/json"),
+    ("map", "application/json"),
+    ("webmanifest", "application/manifest+json"),
+    ("wasm", "application/wasm"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("gz", "application/gzip"),
+    ("tar", "application/x-tar"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("ico", "image/x-icon"),
+    ("bmp", "image/bmp"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("ttf", "font/ttf"),
+    ("otf", "font/otf"),
+    ("mp3", "audio/mpeg"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+];
+
+/// Magic numbers checked by `MimeTypes::sniff`, in order.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"\0asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+];
+
+/// Maps file extensions to media types for the Content-Type header.
+///
+/// Starts with a table of common web types. Text types get
+/// `charset=utf-8` added unless the type already has parameters.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MimeTypes {
+    by_extension: HashMap<String, String>,
+}
+
+impl MimeTypes {
+    /// Creates a table with the built-in types.
+    pub fn new() -> MimeTypes {
+        MimeTypes {
+            by_extension: BUILTIN
+                .iter()
+                .map(|(ext, mime)| (ext.to_string(), mime.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Creates a table with no types in it.
+    pub fn empty() -> MimeTypes {
+        MimeTypes {
+            by_extension: HashMap::new(),
+        }
+    }
+
+    /// Maps extension to mime, replacing any existing mapping. extension is
+    /// matched case-insensitively, with or without a leading `.`.
+    pub fn insert(&mut self, extension: &str, mime: impl Into<String>) {
+        self.by_extension.insert(normalize(extension), mime.into());
+    }
+
+    /// Returns the media type for extension, as it is stored.
+    pub fn get(&self, extension: &str) -> Option<&str> {
+        self.by_extension
+            .get(&normalize(extension))
+            .map(String::as_str)
+    }
+
+    /// Returns the Content-Type for path from its extension.
+    pub fn lookup(&self, path: &Path) -> Option<String> {
+        let extension = path.extension()?.to_str()?;
+        self.get(extension).map(with_charset)
+    }
+
+    /// Guesses a Content-Type from the first bytes of a file.
+    ///
+    /// Recognises common binary formats by their magic numbers and HTML by
+    /// its opening tag. Anything else that looks like UTF-8 text is
+    /// `text/plain`, and the rest is `application/octet-stream`.
+    pub fn sniff(bytes: &[u8]) -> String {
+        if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+            return mime.to_string();
+        }
+        if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            return "image/webp".to_string();
+        }
+        if !is_text(bytes) {
+            return "application/octet-stream".to_string();
+        }
+        let start = String::from_utf8_lossy(&bytes[..bytes.len().min(64)])
+            .trim_start_matches('\u{feff}')
+            .trim_start()
+            .to_ascii_lowercase();
+        let mime = if start.starts_with("<!doctype html") || start.starts_with("<html") {
+            "text/html"
+        } else if start.starts_with("<svg") {
+            "image/svg+xml"
+        } else if start.starts_with("<?xml") {
+            "text/xml"
+        } else {
+            "text/plain"
+        };
+        with_charset(mime)
+    }
+}
+
+impl Default for MimeTypes {
+    fn default() -> MimeTypes {
+        MimeTypes::new()
+    }
+}
+
+fn normalize(extension: &str) -> String {
+    extension.trim_start_matches('.').to_ascii_lowercase()
+}
+
+fn with_charset(mime: &str) -> String {
+    if mime.starts_with("text/") && !mime.contains(';') {
+        format!("{}; charset=utf-8", mime)
+    } else {
+        mime.to_string()
+    }
+}
+
+/// Returns true if bytes is UTF-8 without control characters other than
+/// whitespace. A character cut off at the end is allowed.
+fn is_text(bytes: &[u8]) -> bool {
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) if e.error_len().is_none() => {
+            // Everything up to valid_up_to is UTF-8, so this can't fail.
+            std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap()
+        }
+        Err(_) => return false,
+    };
+    text.chars()
+        .all(|c| !c.is_control() || matches!(c, '\t' | '\n' | '\r' | '\x0c' | '\x1b'))
+}
@@ -0,0 +1,229 @@
+use std::{
+    error::Error,
+    fmt,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex,
+    },
+};
+
+/// What `ThreadPool::execute` does when a bounded queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QueuePolicy {
+    /// Wait until a worker takes a job off the queue.
+    #[default]
+    Block,
+    /// Drop the job. Use `ThreadPool::try_execute` to get it back instead.
+    Reject,
+    /// Run the job on the calling thread.
+    CallerRuns,
+}
+
+/// Returned by `ThreadPool::try_execute` when the queue has no room.
+///
+/// The job is handed back so the caller can retry, run it or discard it.
+pub struct TryExecuteError<F> {
+    job: F,
+}
+
+impl<F> TryExecuteError<F> {
+    pub(crate) fn new(job: F) -> TryExecuteError<F> {
+        TryExecuteError { job }
+    }
+
+    /// Gives back the job that could not be queued.
+    pub fn into_job(self) -> F {
+        self.job
+    }
+}
+
+impl<F> fmt::Debug for TryExecuteError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TryExecuteError").finish_non_exhaustive()
+    }
+}
+
+impl<F> fmt::Display for TryExecuteError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "thread pool queue is full")
+    }
+}
+
+impl<F> Error for TryExecuteError<F> {}
+
+/// Counts queued jobs and enforces the queue's capacity, if it has one.
+pub(crate) struct Capacity {
+    limit: Option<usize>,
+    queued: AtomicUsize,
+    lock: Mutex<()>,
+    space: Condvar,
+}
+
+impl Capacity {
+    pub(crate) fn new(limit: Option<usize>) -> Capacity {
+        Capacity {
+            limit,
+            queued: AtomicUsize::new(0),
+            lock: Mutex::new(()),
+            space: Condvar::new(),
+        }
+    }
+
+    /// Number of jobs sent but not yet picked up by a worker.
+    pub(crate) fn len(&self) -> usize {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Claims a queue slot without blocking.
+    pub(crate) fn try_reserve(&self) -> bool {
+        let limit = match self.limit {
+            Some(limit) => limit,
+            None => {
+                self.queued.fetch_add(1, Ordering::SeqCst);
+                return true;
+            }
+        };
+        self.queued
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |queued| {
+                (queued < limit).then_some(queued + 1)
+            })
+            .is_ok()
+    }
+
+    /// Claims a queue slot, waiting for one to free up if necessary.
+    pub(crate) fn reserve(&self) {
+        if self.try_reserve() {
+            return;
+        }
+        let mut guard = self.lock.lock().unwrap();
+        while !self.try_reserve() {
+            guard = self.space.wait(guard).unwrap();
+        }
+    }
+
+    /// Frees a slot once a worker has taken a job off the queue.
+    pub(crate) fn release(&self) {
+        self.queued.fetch_sub(1, Ordering::SeqCst);
+        if self.limit.is_some() {
+            let _guard = self.lock.lock().unwrap();
+            self.space.notify_one();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{wait_until, Gate},
+        PoolEvent, ThreadPool, ThreadPoolBuilder,
+    };
+    use std::{
+        sync::{atomic::AtomicBool, mpsc, Arc},
+        thread,
+        time::Duration,
+    };
+
+    /// Makes a one-worker pool with room for one queued job, and fills it:
+    /// the worker waits on the returned gate and one job is queued.
+    fn full_pool(policy: QueuePolicy, rejected: Arc<AtomicUsize>) -> (ThreadPool, Gate) {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(1)
+            .queue_capacity(1)
+            .queue_policy(policy)
+            .event_listener(move |event: &PoolEvent<'_>| {
+                if let PoolEvent::JobRejected = event {
+                    rejected.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+            .build()
+            .unwrap();
+        let gate = Gate::new();
+        pool.execute({
+            let gate = gate.clone();
+            move || gate.wait()
+        });
+        wait_until("the worker takes the first job", || pool.queued() == 0);
+        pool.execute(|| {});
+        assert_eq!(pool.queued(), 1);
+        (pool, gate)
+    }
+
+    #[test]
+    fn reject_drops_jobs_when_full() {
+        let rejected = Arc::new(AtomicUsize::new(0));
+        let (pool, gate) = full_pool(QueuePolicy::Reject, Arc::clone(&rejected));
+        let ran = Arc::new(AtomicBool::new(false));
+        pool.execute({
+            let ran = Arc::clone(&ran);
+            move || ran.store(true, Ordering::SeqCst)
+        });
+        assert_eq!(rejected.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.queued(), 1);
+
+        gate.open();
+        drop(pool);
+        assert!(!ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn caller_runs_runs_jobs_on_the_calling_thread() {
+        let rejected = Arc::new(AtomicUsize::new(0));
+        let (pool, gate) = full_pool(QueuePolicy::CallerRuns, Arc::clone(&rejected));
+        let caller = thread::current().id();
+        let (tx, rx) = mpsc::channel();
+        pool.execute(move || tx.send(thread::current().id()).unwrap());
+        assert_eq!(rx.try_recv(), Ok(caller));
+        assert_eq!(rejected.load(Ordering::SeqCst), 0);
+        gate.open();
+    }
+
+    #[test]
+    fn block_waits_for_room() {
+        let (pool, gate) = full_pool(QueuePolicy::Block, Arc::default());
+        let pool = Arc::new(pool);
+        let queued = Arc::new(AtomicBool::new(false));
+        let producer = thread::spawn({
+            let pool = Arc::clone(&pool);
+            let queued = Arc::clone(&queued);
+            move || {
+                pool.execute(|| {});
+                queued.store(true, Ordering::SeqCst);
+            }
+        });
+        thread::sleep(Duration::from_millis(20));
+        assert!(!queued.load(Ordering::SeqCst));
+
+        gate.open();
+        producer.join().unwrap();
+        assert!(queued.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn try_execute_hands_the_job_back() {
+        let (pool, gate) = full_pool(QueuePolicy::Block, Arc::default());
+        let ran = Arc::new(AtomicBool::new(false));
+        let error = pool
+            .try_execute({
+                let ran = Arc::clone(&ran);
+                move || ran.store(true, Ordering::SeqCst)
+            })
+            .unwrap_err();
+        assert_eq!(error.to_string(), "thread pool queue is full");
+        (error.into_job())();
+        assert!(ran.load(Ordering::SeqCst));
+
+        gate.open();
+        wait_until("the queue empties", || pool.queued() == 0);
+        assert!(pool.try_execute(|| ()).is_ok());
+    }
+
+    #[test]
+    fn bounded_pool_needs_room() {
+        assert!(
+            std::panic::catch_unwind(|| ThreadPool::bounded(1, 0, QueuePolicy::Block)).is_err()
+        );
+        let pool = ThreadPool::bounded(2, 1, QueuePolicy::Reject);
+        assert_eq!(pool.submit(|| 3).join().unwrap(), 3);
+    }
+}
@@ -0,0 +1,323 @@
+use std::{
+    collections::{hash_map::RandomState, VecDeque},
+    fs::File,
+    hash::{BuildHasher, Hasher},
+    io::{self, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+/// Requests with more ranges than this are served in full, so a client
+/// can't make us send thousands of tiny parts.
+const MAX_RANGES: usize = 64;
+
+/// The outcome of reading a `Range` header against a representation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Ranges {
+    /// The header should be ignored and the whole representation sent.
+    Ignore,
+    /// None of the ranges overlap the representation: answer 416.
+    Unsatisfiable,
+    /// The satisfiable ranges, in the order to send them.
+    Satisfiable(Vec<Range<u64>>),
+}
+
+/// Parses a `Range` header for a representation of len bytes.
+///
+/// Headers with a unit other than `bytes`, or that don't parse, are
+/// ignored as RFC 9110 14.2 allows. Overlapping ranges are merged.
+pub(crate) fn parse(header: &str, len: u64) -> Ranges {
+    let specs = match header.trim().strip_prefix("bytes=") {
+        Some(specs) => specs,
+        None => return Ranges::Ignore,
+    };
+    if specs.trim().is_empty() {
+        return Ranges::Ignore;
+    }
+
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let (first, last) = match spec.split_once('-') {
+            Some(bounds) => bounds,
+            None => return Ranges::Ignore,
+        };
+        let range = if first.is_empty() {
+            // A suffix range: the last n bytes.
+            let n = match parse_u64(last) {
+                Some(n) => n,
+                None => return Ranges::Ignore,
+            };
+            if n == 0 {
+                continue;
+            }
+            len.saturating_sub(n)..len
+        } else {
+            let first = match parse_u64(first) {
+                Some(first) => first,
+                None => return Ranges::Ignore,
+            };
+            let end = if last.is_empty() {
+                len
+            } else {
+                match parse_u64(last) {
+                    Some(last) if last >= first => last.saturating_add(1).min(len),
+                    _ => return Ranges::Ignore,
+                }
+            };
+            first..end
+        };
+        if range.start < range.end {
+            ranges.push(range);
+        }
+        if ranges.len() > MAX_RANGES {
+            return Ranges::Ignore;
+        }
+    }
+
+    if ranges.is_empty() {
+        return Ranges::Unsatisfiable;
+    }
+    Ranges::Satisfiable(merge_overlapping(ranges))
+}
+
+fn parse_u64(s: &str) -> Option<u64> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Keeps the requested order unless some ranges overlap, in which case
+/// they are sorted and the overlapping ones joined.
+fn merge_overlapping(ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    let mut sorted = ranges.clone();
+    sorted.sort_by_key(|r| r.start);
+    if sorted.windows(2).all(|w| w[0].end <= w[1].start) {
+        return ranges;
+    }
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(sorted.len());
+    for range in sorted {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+/// Formats a `Content-Range` value for range of a len-byte representation.
+pub(crate) fn content_range(range: &Range<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{}", range.start, range.end - 1, len)
+}
+
+/// A `multipart/byteranges` body read from a file one range at a time.
+pub(crate) struct Multipart {
+    file: File,
+    parts: VecDeque<Part>,
+    /// Bytes left in the file range being read, if one is.
+    remaining: Option<u64>,
+}
+
+enum Part {
+    Text(Cursor<Vec<u8>>),
+    File(Range<u64>),
+}
+
+impl Multipart {
+    /// Builds the body for ranges of file, a len-byte representation of
+    /// content_type. Returns the body, its boundary and its length.
+    pub(crate) fn new(
+        file: File,
+        ranges: &[Range<u64>],
+        content_type: &str,
+        len: u64,
+    ) -> (Multipart, String, u64) {
+        let boundary = format!("{:016x}", RandomState::new().build_hasher().finish());
+        let mut parts = VecDeque::new();
+        let mut length = 0;
+        for (i, range) in ranges.iter().enumerate() {
+            let head = format!(
+                "{}--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+                if i == 0 { "" } else { "\r\n" },
+                boundary,
+                content_type,
+                content_range(range, len)
+            );
+            length += head.len() as u64 + (range.end - range.start);
+            parts.push_back(Part::Text(Cursor::new(head.into_bytes())));
+            parts.push_back(Part::File(range.clone()));
+        }
+        let tail = format!("\r\n--{}--\r\n", boundary);
+        length += tail.len() as u64;
+        parts.push_back(Part::Text(Cursor::new(tail.into_bytes())));
+
+        let body = Multipart {
+            file,
+            parts,
+            remaining: None,
+        };
+        (body, boundary, length)
+    }
+}
+
+impl Read for Multipart {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        while let Some(part) = self.parts.front_mut() {
+            match part {
+                Part::Text(text) => {
+                    let read = text.read(buf)?;
+                    if read > 0 {
+                        return Ok(read);
+                    }
+                }
+                Part::File(range) => {
+                    let remaining = match self.remaining {
+                        Some(remaining) => remaining,
+                        None => {
+                            self.file.seek(SeekFrom::Start(range.start))?;
+                            range.end - range.start
+                        }
+                    };
+                    let max = buf
+                        .len()
+                        .min(usize::try_from(remaining).unwrap_or(usize::MAX));
+                    let read = self.file.read(&mut buf[..max])?;
+                    if read == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "file shrank while it was being sent",
+                        ));
+                    }
+                    let remaining = remaining - read as u64;
+                    self.remaining = (remaining > 0).then_some(remaining);
+                    if remaining == 0 {
+                        self.parts.pop_front();
+                    }
+                    return Ok(read);
+                }
+            }
+            self.parts.pop_front();
+        }
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+
+    /// The ranges as (start, end) pairs, end exclusive.
+    fn satisfiable(ranges: &[(u64, u64)]) -> Ranges {
+        Ranges::Satisfiable(ranges.iter().map(|&(start, end)| start..end).collect())
+    }
+
+    #[test]
+    fn parses_simple_ranges() {
+        assert_eq!(parse("bytes=0-499", 1000), satisfiable(&[(0, 500)]));
+        assert_eq!(parse("bytes=500-", 1000), satisfiable(&[(500, 1000)]));
+        assert_eq!(parse(" bytes=900-5000 ", 1000), satisfiable(&[(900, 1000)]));
+        assert_eq!(
+            parse("bytes=0-0, 999-999", 1000),
+            satisfiable(&[(0, 1), (999, 1000)])
+        );
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse("bytes=-100", 1000), satisfiable(&[(900, 1000)]));
+        assert_eq!(parse("bytes=-5000", 1000), satisfiable(&[(0, 1000)]));
+        assert_eq!(parse("bytes=-0", 1000), Ranges::Unsatisfiable);
+    }
+
+    #[test]
+    fn keeps_the_requested_order() {
+        assert_eq!(
+            parse("bytes=500-599,0-99", 1000),
+            satisfiable(&[(500, 600), (0, 100)])
+        );
+        // Adjacent ranges don't overlap.
+        assert_eq!(
+            parse("bytes=100-199,0-99", 1000),
+            satisfiable(&[(100, 200), (0, 100)])
+        );
+    }
+
+    #[test]
+    fn merges_overlapping_ranges() {
+        assert_eq!(
+            parse("bytes=500-599,0-99,50-149,-450", 1000),
+            satisfiable(&[(0, 150), (500, 1000)])
+        );
+        assert_eq!(parse("bytes=0-,0-,0-", 1000), satisfiable(&[(0, 1000)]));
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_unsatisfiable() {
+        assert_eq!(parse("bytes=1000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=1000-1999, 5000-", 1000), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=0-", 0), Ranges::Unsatisfiable);
+        assert_eq!(parse("bytes=-10", 0), Ranges::Unsatisfiable);
+        // One satisfiable range is enough.
+        assert_eq!(parse("bytes=2000-, 0-9", 1000), satisfiable(&[(0, 10)]));
+    }
+
+    #[test]
+    fn ignores_what_it_cannot_parse() {
+        for header in [
+            "items=0-9",
+            "bytes=",
+            "bytes=5",
+            "bytes=9-0",
+            "bytes=a-b",
+            "bytes=+1-2",
+            "bytes=0-9,x",
+            "bytes=--1",
+            "bytes=99999999999999999999-",
+        ] {
+            assert_eq!(parse(header, 1000), Ranges::Ignore, "{:?}", header);
+        }
+    }
+
+    #[test]
+    fn too_many_ranges_are_ignored() {
+        let specs = |n| {
+            (0..n)
+                .map(|i| format!("{}-{}", i * 2, i * 2))
+                .collect::<Vec<_>>()
+        };
+        let header = format!("bytes={}", specs(MAX_RANGES).join(","));
+        assert!(matches!(parse(&header, 1000), Ranges::Satisfiable(r) if r.len() == MAX_RANGES));
+        let header = format!("bytes={}", specs(MAX_RANGES + 1).join(","));
+        assert_eq!(parse(&header, 1000), Ranges::Ignore);
+    }
+
+    #[test]
+    fn formats_content_range() {
+        assert_eq!(content_range(&(0..500), 1000), "bytes 0-499/1000");
+        assert_eq!(content_range(&(999..1000), 1000), "bytes 999-999/1000");
+    }
+
+    #[test]
+    fn multipart_body_has_every_range() {
+        let path = env::temp_dir().join(format!("websvr-range-{}", process::id()));
+        fs::write(&path, "0123456789").unwrap();
+        let file = File::open(&path).unwrap();
+
+        let (mut body, boundary, len) = Multipart::new(file, &[7..10, 0..2], "text/plain", 10);
+        let mut read = String::new();
+        body.read_to_string(&mut read).unwrap();
+        drop(body);
+        fs::remove_file(&path).unwrap();
+        let expected = format!(
+            "--{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 7-9/10\r\n\r\n789\r\n\
+             --{b}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+             --{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(read, expected);
+        assert_eq!(len, expected.len() as u64);
+    }
+}
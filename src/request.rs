@@ -0,0 +1,689 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, Read},
+    str::FromStr,
+};
+
+use crate::{chunked, headers, Headers, Params, StatusCode};
+
+/// An HTTP request method.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    /// Any other method token, e.g. a WebDAV method.
+    Other(String),
+}
+
+impl Method {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Connect => "CONNECT",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Patch => "PATCH",
+            Method::Other(method) => method,
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = ParseError;
+
+    /// Parses a method. Methods are case-sensitive, so `get` is an unknown
+    /// method rather than GET.
+    fn from_str(s: &str) -> Result<Method, ParseError> {
+        Ok(match s {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "CONNECT" => Method::Connect,
+            "OPTIONS" => Method::Options,
+            "TRACE" => Method::Trace,
+            "PATCH" => Method::Patch,
+            other if headers::is_token(other) => Method::Other(other.to_string()),
+            _ => return Err(ParseError::BadRequest("invalid method")),
+        })
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An HTTP protocol version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        }
+    }
+}
+
+impl FromStr for Version {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Version, ParseError> {
+        match s {
+            "HTTP/1.0" => Ok(Version::Http10),
+            "HTTP/1.1" => Ok(Version::Http11),
+            _ if s.starts_with("HTTP/") => Err(ParseError::VersionNotSupported),
+            _ => Err(ParseError::BadRequest("invalid HTTP version")),
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Size limits applied while reading a Request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// Longest request line, in bytes. Longer lines are a 414 URI Too Long.
+    pub max_request_line: usize,
+    /// Largest total size of all header lines, in bytes. Larger headers are
+    /// a 431 Request Header Fields Too Large.
+    pub max_header_bytes: usize,
+    /// Most header lines. More are a 431 Request Header Fields Too Large.
+    pub max_headers: usize,
+    /// Largest body, in bytes, after any chunked coding is removed. Larger
+    /// bodies are a 413 Content Too Large.
+    pub max_body: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_request_line: 8 * 1024,
+            max_header_bytes: 16 * 1024,
+            max_headers: 100,
+            max_body: 1024 * 1024,
+        }
+    }
+}
+
+/// A parsed HTTP/1.x request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Request {
+    method: Method,
+    target: String,
+    version: Version,
+    headers: Headers,
+    body: Vec<u8>,
+    trailers: Headers,
+    params: Params,
+}
+
+impl Request {
+    /// Reads one request from reader using the default Limits.
+    pub fn read_from<R: BufRead>(reader: &mut R) -> Result<Request, ParseError> {
+        Request::read_with_limits(reader, &Limits::default())
+    }
+
+    /// Reads one request from reader, enforcing limits.
+    ///
+    /// Returns `ParseError::ConnectionClosed` if the reader is at EOF before
+    /// the request starts.
+    pub fn read_with_limits<R: BufRead>(
+        reader: &mut R,
+        limits: &Limits,
+    ) -> Result<Request, ParseError> {
+        // Servers should ignore empty lines before the request line (RFC 9112 2.2).
+        let line = loop {
+            match read_line(reader, limits.max_request_line)? {
+                Line::Eof => return Err(ParseError::ConnectionClosed),
+                Line::TooLong => return Err(ParseError::UriTooLong),
+                Line::Complete(line) if line.is_empty() => continue,
+                Line::Complete(line) => break line,
+            }
+        };
+        let (method, target, version) = parse_request_line(&line)?;
+        let headers = read_fields(reader, limits)?;
+        let (body, trailers) = read_body(reader, version, &headers, limits)?;
+
+        Ok(Request {
+            method,
+            target,
+            version,
+            headers,
+            body,
+            trailers,
+            params: Params::default(),
+        })
+    }
+
+    pub fn method(&self) -> &Method {
+        &self.method
+    }
+
+    /// The request target exactly as sent, e.g. `/search?q=rust`.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The path part of the target, without the query, e.g. `/search`.
+    ///
+    /// For absolute-form targets like `http://host/a` this is `/a`.
+    pub fn path(&self) -> &str {
+        let target = strip_authority(&self.target);
+        match target.find('?') {
+            Some(i) => &target[..i],
+            None => target,
+        }
+    }
+
+    /// The query part of the target, without the `?`, if there is one.
+    pub fn query(&self) -> Option<&str> {
+        let target = strip_authority(&self.target);
+        target.find('?').map(|i| &target[i + 1..])
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// Returns the first value of the named header.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// The trailer fields sent after a chunked body. Empty for other
+    /// bodies.
+    pub fn trailers(&self) -> &Headers {
+        &self.trailers
+    }
+
+    /// Returns the named path parameter captured by the Router.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.get(name)
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    pub(crate) fn set_params(&mut self, params: Params) {
+        self.params = params;
+    }
+}
+
+/// Why a request could not be read.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The connection closed before a request started.
+    ConnectionClosed,
+    /// Reading from the connection failed.
+    Io(io::Error),
+    /// The request is malformed.
+    BadRequest(&'static str),
+    /// The request line is longer than `Limits::max_request_line`.
+    UriTooLong,
+    /// The headers exceed `Limits::max_header_bytes` or `Limits::max_headers`.
+    HeadersTooLarge,
+    /// The body is longer than `Limits::max_body`.
+    BodyTooLarge,
+    /// The request uses a transfer coding this server cannot decode.
+    NotImplemented(&'static str),
+    /// The request uses an HTTP version other than 1.0 or 1.1.
+    VersionNotSupported,
+}
+
+impl ParseError {
+    /// The status code to answer the client with, or None if no response
+    /// should be sent because the connection is gone.
+    pub fn status_code(&self) -> Option<StatusCode> {
+        match self {
+            ParseError::ConnectionClosed | ParseError::Io(_) => None,
+            ParseError::BadRequest(_) => Some(StatusCode::BadRequest),
+            ParseError::BodyTooLarge => Some(StatusCode::ContentTooLarge),
+            ParseError::UriTooLong => Some(StatusCode::UriTooLong),
+            ParseError::HeadersTooLarge => Some(StatusCode::RequestHeaderFieldsTooLarge),
+            ParseError::NotImplemented(_) => Some(StatusCode::NotImplemented),
+            ParseError::VersionNotSupported => Some(StatusCode::HttpVersionNotSupported),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::ConnectionClosed => write!(f, "connection closed before request"),
+            ParseError::Io(e) => write!(f, "failed to read request: {}", e),
+            ParseError::BadRequest(reason) => write!(f, "bad request: {}", reason),
+            ParseError::UriTooLong => write!(f, "request line too long"),
+            ParseError::HeadersTooLarge => write!(f, "request headers too large"),
+            ParseError::BodyTooLarge => write!(f, "request body too large"),
+            ParseError::NotImplemented(what) => write!(f, "not implemented: {}", what),
+            ParseError::VersionNotSupported => write!(f, "HTTP version not supported"),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> ParseError {
+        ParseError::Io(e)
+    }
+}
+
+pub(crate) enum Line {
+    Complete(String),
+    TooLong,
+    Eof,
+}
+
+/// Reads a line ending in CRLF or a bare LF, without the line ending.
+///
+/// Lines longer than limit bytes are reported as TooLong.
+pub(crate) fn read_line<R: BufRead>(reader: &mut R, limit: usize) -> Result<Line, ParseError> {
+    let mut buf = Vec::new();
+    // Allow for the CRLF on top of the limit.
+    let read = reader.take(limit as u64 + 2).read_until(b'\n', &mut buf)?;
+    if read == 0 {
+        return Ok(Line::Eof);
+    }
+    if buf.last() != Some(&b'\n') {
+        if read >= limit + 2 {
+            return Ok(Line::TooLong);
+        }
+        return Err(ParseError::BadRequest("unexpected end of request"));
+    }
+    buf.pop();
+    if buf.last() == Some(&b'\r') {
+        buf.pop();
+    }
+    if buf.len() > limit {
+        return Ok(Line::TooLong);
+    }
+    String::from_utf8(buf)
+        .map(Line::Complete)
+        .map_err(|_| ParseError::BadRequest("request is not valid UTF-8"))
+}
+
+fn parse_request_line(line: &str) -> Result<(Method, String, Version), ParseError> {
+    let mut parts = line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(method), Some(target), Some(version), None) => (method, target, version),
+        _ => return Err(ParseError::BadRequest("malformed request line")),
+    };
+    let method = method.parse()?;
+    let version = version.parse()?;
+    if !is_valid_target(target) {
+        return Err(ParseError::BadRequest("invalid request target"));
+    }
+    Ok((method, target.to_string(), version))
+}
+
+fn is_valid_target(target: &str) -> bool {
+    let form_ok = target.starts_with('/')
+        || target == "*"
+        || target.starts_with("http://")
+        || target.starts_with("https://");
+    form_ok && target.bytes().all(|b| b.is_ascii_graphic())
+}
+
+fn strip_authority(target: &str) -> &str {
+    for scheme in ["http://", "https://"] {
+        if let Some(rest) = target.strip_prefix(scheme) {
+            return match rest.find('/') {
+                Some(i) => &rest[i..],
+                None => "/",
+            };
+        }
+    }
+    target
+}
+
+/// Reads header fields up to and including the empty line that ends them.
+pub(crate) fn read_fields<R: BufRead>(
+    reader: &mut R,
+    limits: &Limits,
+) -> Result<Headers, ParseError> {
+    let mut fields = Headers::new();
+    let mut bytes = 0;
+    loop {
+        let remaining = limits.max_header_bytes.saturating_sub(bytes);
+        let line = match read_line(reader, remaining)? {
+            Line::Eof => return Err(ParseError::BadRequest("unexpected end of headers")),
+            Line::TooLong => return Err(ParseError::HeadersTooLarge),
+            Line::Complete(line) => line,
+        };
+        if line.is_empty() {
+            return Ok(fields);
+        }
+        bytes += line.len() + 2;
+        if fields.len() == limits.max_headers {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        let (name, value) = parse_header(&line)?;
+        fields.append(name, value);
+    }
+}
+
+fn parse_header(line: &str) -> Result<(&str, &str), ParseError> {
+    if line.starts_with([' ', '\t']) {
+        return Err(ParseError::BadRequest("obsolete header line folding"));
+    }
+    let (name, value) = line
+        .split_once(':')
+        .ok_or(ParseError::BadRequest("header line without a colon"))?;
+    if !headers::is_token(name) {
+        return Err(ParseError::BadRequest("invalid header name"));
+    }
+    Ok((name, value.trim_matches([' ', '\t'])))
+}
+
+/// Reads the body and any trailer fields, framed as RFC 9112 6.3 describes.
+fn read_body<R: BufRead>(
+    reader: &mut R,
+    version: Version,
+    headers: &Headers,
+    limits: &Limits,
+) -> Result<(Vec<u8>, Headers), ParseError> {
+    if headers.contains("Transfer-Encoding") {
+        if version == Version::Http10 {
+            return Err(ParseError::BadRequest(
+                "Transfer-Encoding in an HTTP/1.0 request",
+            ));
+        }
+        // Both headers together are a request smuggling risk, so refuse
+        // rather than pick one.
+        if headers.contains("Content-Length") {
+            return Err(ParseError::BadRequest(
+                "both Transfer-Encoding and Content-Length",
+            ));
+        }
+        let codings: Vec<&str> = headers.values("Transfer-Encoding").collect();
+        return match codings[..] {
+            [coding] if coding.eq_ignore_ascii_case("chunked") => {
+                chunked::read_chunked(reader, limits)
+            }
+            [.., last] if last.eq_ignore_ascii_case("chunked") => Err(ParseError::NotImplemented(
+                "transfer codings other than chunked",
+            )),
+            _ => Err(ParseError::BadRequest(
+                "chunked is not the final transfer coding",
+            )),
+        };
+    }
+
+    // values() skips empty elements, so a field with none would otherwise
+    // read as no body at all, leaving the body to be read as a request.
+    if headers
+        .get_all("Content-Length")
+        .any(|value| value.split(',').all(|element| element.trim().is_empty()))
+    {
+        return Err(ParseError::BadRequest("empty Content-Length"));
+    }
+    let mut lengths = headers.values("Content-Length");
+    let length = match lengths.next() {
+        None => return Ok((Vec::new(), Headers::new())),
+        Some(length) => length,
+    };
+    // Repeated Content-Length values must all agree (RFC 9112 6.3).
+    if lengths.any(|other| other != length) {
+        return Err(ParseError::BadRequest("conflicting Content-Length"));
+    }
+    let length: usize = length
+        .bytes()
+        .all(|b| b.is_ascii_digit())
+        .then(|| length.parse().ok())
+        .flatten()
+        .ok_or(ParseError::BadRequest("invalid Content-Length"))?;
+    if length > limits.max_body {
+        return Err(ParseError::BodyTooLarge);
+    }
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => ParseError::BadRequest("body shorter than Content-Length"),
+        _ => ParseError::Io(e),
+    })?;
+    Ok((body, Headers::new()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(input: &str) -> Result<Request, ParseError> {
+        read_limited(input, &Limits::default())
+    }
+
+    fn read_limited(input: &str, limits: &Limits) -> Result<Request, ParseError> {
+        Request::read_with_limits(&mut input.as_bytes(), limits)
+    }
+
+    #[test]
+    fn reads_a_request() {
+        let request = read(
+            "\r\nPOST http://example.com/a?b=c HTTP/1.1\r\nHost: example.com\r\n\
+             X-Padded: \t value \r\nContent-Length: 5\r\n\r\nhello",
+        )
+        .unwrap();
+        assert_eq!(request.method(), &Method::Post);
+        assert_eq!(request.path(), "/a");
+        assert_eq!(request.query(), Some("b=c"));
+        assert_eq!(request.version(), Version::Http11);
+        assert_eq!(request.header("x-padded"), Some("value"));
+        assert_eq!(request.body(), b"hello");
+    }
+
+    #[test]
+    fn accepts_bare_line_feeds() {
+        let request = read("GET / HTTP/1.0\nHost: a\n\n").unwrap();
+        assert_eq!(request.header("Host"), Some("a"));
+    }
+
+    #[test]
+    fn empty_input_is_a_closed_connection() {
+        assert!(matches!(read(""), Err(ParseError::ConnectionClosed)));
+        assert!(matches!(read("\r\n"), Err(ParseError::ConnectionClosed)));
+    }
+
+    #[test]
+    fn rejects_malformed_request_lines() {
+        for line in [
+            "GET /",
+            "GET  / HTTP/1.1",
+            "GET / HTTP/1.1 extra",
+            "GET relative HTTP/1.1",
+            "GET /a\x01b HTTP/1.1",
+            "G(T / HTTP/1.1",
+            "GET / http/1.1",
+        ] {
+            let result = read(&format!("{}\r\n\r\n", line));
+            assert!(
+                matches!(result, Err(ParseError::BadRequest(_))),
+                "{:?} gave {:?}",
+                line,
+                result
+            );
+        }
+        assert!(matches!(
+            read("GET / HTTP/2.0\r\n\r\n"),
+            Err(ParseError::VersionNotSupported)
+        ));
+        assert!(matches!(
+            read("GET / HTTP/1.1\r\n"),
+            Err(ParseError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_headers() {
+        for header in ["No colon", " Folded: line", "Bad name: x", "Bad\x7f: x"] {
+            let result = read(&format!("GET / HTTP/1.1\r\n{}\r\n\r\n", header));
+            assert!(
+                matches!(result, Err(ParseError::BadRequest(_))),
+                "{:?} gave {:?}",
+                header,
+                result
+            );
+        }
+    }
+
+    #[test]
+    fn enforces_request_line_limit() {
+        let limits = Limits {
+            max_request_line: 18,
+            ..Limits::default()
+        };
+        assert!(read_limited("GET /0123 HTTP/1.1\r\n\r\n", &limits).is_ok());
+        assert!(matches!(
+            read_limited("GET /01234 HTTP/1.1\r\n\r\n", &limits),
+            Err(ParseError::UriTooLong)
+        ));
+        assert!(matches!(
+            read_limited(&format!("GET /{} HTTP/1.1", "a".repeat(100)), &limits),
+            Err(ParseError::UriTooLong)
+        ));
+    }
+
+    #[test]
+    fn enforces_header_limits() {
+        let limits = Limits {
+            max_header_bytes: 20,
+            max_headers: 2,
+            ..Limits::default()
+        };
+        assert!(read_limited("GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\n\r\n", &limits).is_ok());
+        assert!(matches!(
+            read_limited(
+                "GET / HTTP/1.1\r\nA: 12345678\r\nB: 12345678\r\n\r\n",
+                &limits
+            ),
+            Err(ParseError::HeadersTooLarge)
+        ));
+        assert!(matches!(
+            read_limited("GET / HTTP/1.1\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n", &limits),
+            Err(ParseError::HeadersTooLarge)
+        ));
+    }
+
+    #[test]
+    fn enforces_body_limit() {
+        let limits = Limits {
+            max_body: 4,
+            ..Limits::default()
+        };
+        assert!(read_limited("POST / HTTP/1.1\r\nContent-Length: 4\r\n\r\nabcd", &limits).is_ok());
+        assert!(matches!(
+            read_limited("POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nabcde", &limits),
+            Err(ParseError::BodyTooLarge)
+        ));
+        assert!(matches!(
+            read_limited(
+                "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nabcde\r\n0\r\n\r\n",
+                &limits
+            ),
+            Err(ParseError::BodyTooLarge)
+        ));
+    }
+
+    #[test]
+    fn reads_chunked_bodies() {
+        let request = read(
+            "POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+             3\r\nabc\r\n0\r\nX-Sum: 3\r\n\r\n",
+        )
+        .unwrap();
+        assert_eq!(request.body(), b"abc");
+        assert_eq!(request.trailers().get("X-Sum"), Some("3"));
+    }
+
+    #[test]
+    fn rejects_conflicting_framing() {
+        for headers in [
+            "Transfer-Encoding: chunked\r\nContent-Length: 3",
+            "Content-Length: 3\r\nContent-Length: 4",
+            "Content-Length: +3",
+            "Content-Length:",
+            "Content-Length: ,",
+            "Content-Length: 3\r\nContent-Length: ",
+            "Transfer-Encoding: chunked, gzip",
+        ] {
+            let result = read(&format!("POST / HTTP/1.1\r\n{}\r\n\r\nabc", headers));
+            assert!(
+                matches!(result, Err(ParseError::BadRequest(_))),
+                "{:?} gave {:?}",
+                headers,
+                result
+            );
+        }
+        for headers in [
+            "Content-Length: 3\r\nContent-Length: 3",
+            "Content-Length: 3, 3",
+        ] {
+            let request = read(&format!("POST / HTTP/1.1\r\n{}\r\n\r\nabc", headers));
+            assert_eq!(request.unwrap().body(), b"abc");
+        }
+    }
+
+    #[test]
+    fn rejects_transfer_encoding_in_http_1_0() {
+        assert!(matches!(
+            read("POST / HTTP/1.0\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n"),
+            Err(ParseError::BadRequest(_))
+        ));
+    }
+
+    #[test]
+    fn other_transfer_codings_are_not_implemented() {
+        assert!(matches!(
+            read("POST / HTTP/1.1\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n"),
+            Err(ParseError::NotImplemented(_))
+        ));
+    }
+
+    #[test]
+    fn short_body_is_a_bad_request() {
+        assert!(matches!(
+            read("POST / HTTP/1.1\r\nContent-Length: 10\r\n\r\nabc"),
+            Err(ParseError::BadRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,375 @@
+use std::{
+    fmt,
+    io::{self, BufWriter, Read, Write},
+    time::SystemTime,
+};
+
+use crate::{chunked::ChunkedWriter, httpdate, Headers, Method, Request, Version};
+
+macro_rules! status_codes {
+    ($($name:ident = $code:literal, $reason:literal;)*) => {
+        /// An HTTP response status code.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub enum StatusCode {
+            $($name,)*
+        }
+
+        impl StatusCode {
+            /// The numeric code, e.g. 404.
+            pub fn as_u16(self) -> u16 {
+                match self {
+                    $(StatusCode::$name => $code,)*
+                }
+            }
+
+            /// The standard reason phrase, e.g. `Not Found`.
+            pub fn reason_phrase(self) -> &'static str {
+                match self {
+                    $(StatusCode::$name => $reason,)*
+                }
+            }
+
+            /// Returns the StatusCode for a numeric code, if it is one this
+            /// server knows.
+            pub fn from_u16(code: u16) -> Option<StatusCode> {
+                match code {
+                    $($code => Some(StatusCode::$name),)*
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+status_codes! {
+    Continue = 100, "Continue";
+    SwitchingProtocols = 101, "Switching Protocols";
+    Ok = 200, "OK";
+    Created = 201, "Created";
+    Accepted = 202, "Accepted";
+    NoContent = 204, "No Content";
+    PartialContent = 206, "Partial Content";
+    MovedPermanently = 301, "Moved Permanently";
+    Found = 302, "Found";
+    SeeOther = 303, "See Other";
+    NotModified = 304, "Not Modified";
+    TemporaryRedirect = 307, "Temporary Redirect";
+    PermanentRedirect = 308, "Permanent Redirect";
+    BadRequest = 400, "Bad Request";
+    Unauthorized = 401, "Unauthorized";
+    Forbidden = 403, "Forbidden";
+    NotFound = 404, "Not Found";
+    MethodNotAllowed = 405, "Method Not Allowed";
+    NotAcceptable = 406, "Not Acceptable";
+    RequestTimeout = 408, "Request Timeout";
+    Conflict = 409, "Conflict";
+    Gone = 410, "Gone";
+    LengthRequired = 411, "Length Required";
+    PreconditionFailed = 412, "Precondition Failed";
+    ContentTooLarge = 413, "Content Too Large";
+    UriTooLong = 414, "URI Too Long";
+    UnsupportedMediaType = 415, "Unsupported Media Type";
+    RangeNotSatisfiable = 416, "Range Not Satisfiable";
+    ExpectationFailed = 417, "Expectation Failed";
+    TooManyRequests = 429, "Too Many Requests";
+    RequestHeaderFieldsTooLarge = 431, "Request Header Fields Too Large";
+    InternalServerError = 500, "Internal Server Error";
+    NotImplemented = 501, "Not Implemented";
+    BadGateway = 502, "Bad Gateway";
+    ServiceUnavailable = 503, "Service Unavailable";
+    GatewayTimeout = 504, "Gateway Timeout";
+    HttpVersionNotSupported = 505, "HTTP Version Not Supported";
+}
+
+impl StatusCode {
+    /// Returns true for 1xx, 204 and 304 responses, which never have a body.
+    pub fn forbids_body(self) -> bool {
+        let code = self.as_u16();
+        code < 200 || code == 204 || code == 304
+    }
+
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.as_u16())
+    }
+
+    pub fn is_client_error(self) -> bool {
+        (400..500).contains(&self.as_u16())
+    }
+
+    pub fn is_server_error(self) -> bool {
+        self.as_u16() >= 500
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.as_u16(), self.reason_phrase())
+    }
+}
+
+/// Writes a Body::Writer body.
+pub type BodyWriter = Box<dyn FnOnce(&mut dyn Write) -> io::Result<()> + Send>;
+
+/// The body of a Response.
+pub enum Body {
+    Empty,
+    Bytes(Vec<u8>),
+    /// A body read from a reader as it is written out. Without a known
+    /// length it is sent with chunked transfer coding.
+    Stream {
+        reader: Box<dyn Read + Send>,
+        length: Option<u64>,
+    },
+    /// A body produced by a function writing to the connection, sent with
+    /// chunked transfer coding. Writes are buffered; `flush` sends what has
+    /// been written so far as a chunk.
+    Writer(BodyWriter),
+}
+
+impl Body {
+    /// The body's length in bytes, if known up front.
+    pub fn len(&self) -> Option<u64> {
+        match self {
+            Body::Empty => Some(0),
+            Body::Bytes(bytes) => Some(bytes.len() as u64),
+            Body::Stream { length, .. } => *length,
+            Body::Writer(_) => None,
+        }
+    }
+
+    /// Returns true if the body is known to be empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == Some(0)
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Body::Empty => write!(f, "Empty"),
+            Body::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()),
+            Body::Stream { length, .. } => {
+                f.debug_struct("Stream").field("length", length).finish()
+            }
+            Body::Writer(_) => write!(f, "Writer"),
+        }
+    }
+}
+
+impl From<Vec<u8>> for Body {
+    fn from(bytes: Vec<u8>) -> Body {
+        Body::Bytes(bytes)
+    }
+}
+
+impl From<&[u8]> for Body {
+    fn from(bytes: &[u8]) -> Body {
+        Body::Bytes(bytes.to_vec())
+    }
+}
+
+impl From<String> for Body {
+    fn from(s: String) -> Body {
+        Body::Bytes(s.into_bytes())
+    }
+}
+
+impl From<&str> for Body {
+    fn from(s: &str) -> Body {
+        Body::Bytes(s.as_bytes().to_vec())
+    }
+}
+
+/// An HTTP response.
+///
+/// Framing headers (`Content-Length`, `Transfer-Encoding`) are worked out
+/// from the body when the response is written, and a `Date` header is added
+/// if there is none.
+#[derive(Debug)]
+pub struct Response {
+    status: StatusCode,
+    headers: Headers,
+    body: Body,
+}
+
+impl Response {
+    /// Creates a response with no headers and an empty body.
+    pub fn new(status: StatusCode) -> Response {
+        Response {
+            status,
+            headers: Headers::new(),
+            body: Body::Empty,
+        }
+    }
+
+    /// Creates a `text/html` response.
+    pub fn html(status: StatusCode, html: impl Into<String>) -> Response {
+        Response::new(status)
+            .with_header("Content-Type", "text/html; charset=utf-8")
+            .with_body(html.into())
+    }
+
+    /// Creates a `text/plain` response.
+    pub fn text(status: StatusCode, text: impl Into<String>) -> Response {
+        Response::new(status)
+            .with_header("Content-Type", "text/plain; charset=utf-8")
+            .with_body(text.into())
+    }
+
+    /// Adds a header, keeping any existing values of the same header.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Response {
+        self.headers.append(name, value);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Body>) -> Response {
+        self.body = body.into();
+        self
+    }
+
+    /// Sets a body streamed from reader. length is the number of bytes the
+    /// reader will produce, if known.
+    pub fn with_stream<R>(mut self, reader: R, length: Option<u64>) -> Response
+    where
+        R: Read + Send + 'static,
+    {
+        self.body = Body::Stream {
+            reader: Box::new(reader),
+            length,
+        };
+        self
+    }
+
+    /// Sets a body produced by write as the response is sent, for content
+    /// generated progressively. write gets the connection, wrapped in
+    /// chunked coding for HTTP/1.1 clients.
+    pub fn with_writer<F>(mut self, write: F) -> Response
+    where
+        F: FnOnce(&mut dyn Write) -> io::Result<()> + Send + 'static,
+    {
+        self.body = Body::Writer(Box::new(write));
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn set_status(&mut self, status: StatusCode) {
+        self.status = status;
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn set_body(&mut self, body: impl Into<Body>) {
+        self.body = body.into();
+    }
+
+    /// Takes the body, leaving an empty one.
+    pub fn take_body(&mut self) -> Body {
+        std::mem::replace(&mut self.body, Body::Empty)
+    }
+
+    /// Writes the response for an HTTP/1.1 client.
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        self.write(writer, Version::Http11, false)
+    }
+
+    /// Writes the response as an answer to request: the body is left out for
+    /// HEAD requests, and HTTP/1.0 clients never get chunked framing.
+    pub fn write_for<W: Write>(self, request: &Request, writer: &mut W) -> io::Result<()> {
+        let head_only = *request.method() == Method::Head;
+        self.write(writer, request.version(), head_only)
+    }
+
+    fn write<W: Write>(
+        mut self,
+        writer: &mut W,
+        version: Version,
+        head_only: bool,
+    ) -> io::Result<()> {
+        self.headers.remove("Content-Length");
+        self.headers.remove("Transfer-Encoding");
+        if !self.headers.contains("Date") {
+            self.headers
+                .insert("Date", httpdate::format(SystemTime::now()));
+        }
+
+        let mut body = self.take_body();
+        if self.status.forbids_body() {
+            body = Body::Empty;
+        } else {
+            match body.len() {
+                Some(length) => self.headers.insert("Content-Length", length.to_string()),
+                None if version >= Version::Http11 => {
+                    self.headers.insert("Transfer-Encoding", "chunked")
+                }
+                // HTTP/1.0 has no chunked coding, so the body ends when the
+                // connection closes.
+                None => self.headers.insert("Connection", "close"),
+            }
+        }
+
+        let mut head = format!("HTTP/1.1 {}\r\n{}\r\n", self.status, self.headers).into_bytes();
+        if head_only {
+            return writer.write_all(&head);
+        }
+        match body {
+            Body::Empty => writer.write_all(&head)?,
+            Body::Bytes(bytes) => {
+                head.extend_from_slice(&bytes);
+                writer.write_all(&head)?;
+            }
+            Body::Stream {
+                reader,
+                length: Some(length),
+            } => {
+                writer.write_all(&head)?;
+                let copied = io::copy(&mut reader.take(length), writer)?;
+                if copied < length {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "response body shorter than its length",
+                    ));
+                }
+            }
+            Body::Stream {
+                mut reader,
+                length: None,
+            } => {
+                writer.write_all(&head)?;
+                if version >= Version::Http11 {
+                    let mut chunked = ChunkedWriter::new(&mut *writer);
+                    io::copy(&mut reader, &mut chunked)?;
+                    chunked.finish()?;
+                } else {
+                    io::copy(&mut reader, writer)?;
+                }
+            }
+            Body::Writer(write) => {
+                writer.write_all(&head)?;
+                // Buffered so that small writes don't each become a chunk.
+                if version >= Version::Http11 {
+                    let mut chunked = BufWriter::new(ChunkedWriter::new(&mut *writer));
+                    write(&mut chunked)?;
+                    chunked.into_inner().map_err(|e| e.into_error())?.finish()?;
+                } else {
+                    let mut buffered = BufWriter::new(&mut *writer);
+                    write(&mut buffered)?;
+                    buffered.flush()?;
+                }
+            }
+        }
+        writer.flush()
+    }
+}
@@ -0,0 +1,444 @@
+use std::fmt::Write as _;
+
+use crate::{Handler, Method, Request, Response, StatusCode};
+
+/// Dispatches requests to handlers by method and path.
+///
+/// Patterns are matched segment by segment. A segment starting with `:`
+/// matches any one segment, and a final segment starting with `*` matches
+/// the rest of the path, including nothing:
+///
+/// - `/users/:id` matches `/users/42`, with `id` set to `42`
+/// - `/static/*path` matches `/static/css/site.css`, with `path` set to
+///   `css/site.css`
+///
+/// Routes are tried in the order they were added and the first match wins.
+/// A GET route also answers HEAD. When the path matches a route but the
+/// method does not, the router answers 405 with an `Allow` header; when no
+/// route matches the path it calls the fallback, which answers 404 unless
+/// replaced.
+pub struct Router {
+    routes: Vec<Route>,
+    fallback: Box<dyn Handler>,
+}
+
+struct Route {
+    method: Method,
+    pattern: Vec<Segment>,
+    handler: Box<dyn Handler>,
+}
+
+enum Segment {
+    Literal(String),
+    Param(String),
+    Wildcard(String),
+}
+
+impl Router {
+    pub fn new() -> Router {
+        Router {
+            routes: Vec::new(),
+            fallback: Box::new(|_: &Request| Response::text(StatusCode::NotFound, "Not Found")),
+        }
+    }
+
+    /// Adds a route for requests with method whose path matches pattern.
+    ///
+    /// # Panics
+    ///
+    /// Panics if pattern does not start with `/`, if a parameter has no
+    /// name, or if a wildcard is not the last segment.
+    pub fn route<F>(self, method: Method, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route_handler(method, pattern, handler)
+    }
+
+    /// Like `route`, but takes any Handler, such as a Chain or another
+    /// Router.
+    pub fn route_handler<H>(mut self, method: Method, pattern: &str, handler: H) -> Router
+    where
+        H: Handler + 'static,
+    {
+        self.routes.push(Route {
+            method,
+            pattern: parse_pattern(pattern),
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    pub fn get<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    pub fn put<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    pub fn delete<F>(self, pattern: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    /// Sets the handler for requests whose path matches no route.
+    pub fn fallback<F>(self, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static,
+    {
+        self.fallback_handler(handler)
+    }
+
+    /// Like `fallback`, but takes any Handler.
+    pub fn fallback_handler<H: Handler + 'static>(mut self, handler: H) -> Router {
+        self.fallback = Box::new(handler);
+        self
+    }
+
+    /// Routes request to a handler and returns its response.
+    ///
+    /// The path parameters of the matching route are stored in request
+    /// before the handler is called.
+    pub fn handle(&self, request: &mut Request) -> Response {
+        let mut allowed: Vec<&Method> = Vec::new();
+        for route in &self.routes {
+            let params = match match_path(&route.pattern, request.path()) {
+                Some(params) => params,
+                None => continue,
+            };
+            if route.answers(request.method()) {
+                request.set_params(params);
+                return route.handler.handle(request);
+            }
+            if !allowed.contains(&&route.method) {
+                allowed.push(&route.method);
+            }
+        }
+
+        if allowed.is_empty() {
+            return self.fallback.handle(request);
+        }
+        if allowed.contains(&&Method::Get) && !allowed.contains(&&Method::Head) {
+            allowed.push(&Method::Head);
+        }
+        let mut allow = String::new();
+        for method in allowed {
+            if !allow.is_empty() {
+                allow.push_str(", ");
+            }
+            let _ = write!(allow, "{}", method);
+        }
+        Response::text(StatusCode::MethodNotAllowed, "Method Not Allowed")
+            .with_header("Allow", allow)
+    }
+}
+
+impl Default for Router {
+    fn default() -> Router {
+        Router::new()
+    }
+}
+
+impl Route {
+    fn answers(&self, method: &Method) -> bool {
+        self.method == *method || (self.method == Method::Get && *method == Method::Head)
+    }
+}
+
+/// Path parameters captured by a Router.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Params {
+    entries: Vec<(String, String)>,
+}
+
+impl Params {
+    /// Returns the value of the named parameter, percent-decoded.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|(n, v)| (n.as_str(), v.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    try_parse_pattern(pattern).unwrap_or_else(|e| panic!("{}", e))
+}
+
+/// Returns an error describing what is wrong with pattern, if anything.
+pub(crate) fn check_pattern(pattern: &str) -> Result<(), String> {
+    try_parse_pattern(pattern).map(|_| ())
+}
+
+fn try_parse_pattern(pattern: &str) -> Result<Vec<Segment>, String> {
+    let rest = match pattern.strip_prefix('/') {
+        Some(rest) => rest,
+        None => return Err(format!("route pattern {:?} must start with '/'", pattern)),
+    };
+    let parts: Vec<&str> = rest.split('/').collect();
+    let last = parts.len() - 1;
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if let Some(name) = part.strip_prefix(':') {
+                if name.is_empty() {
+                    return Err(format!("unnamed parameter in route {:?}", pattern));
+                }
+                Ok(Segment::Param(name.to_string()))
+            } else if let Some(name) = part.strip_prefix('*') {
+                if name.is_empty() {
+                    return Err(format!("unnamed wildcard in route {:?}", pattern));
+                }
+                if i != last {
+                    return Err(format!("wildcard must end route {:?}", pattern));
+                }
+                Ok(Segment::Wildcard(name.to_string()))
+            } else {
+                Ok(Segment::Literal(part.to_string()))
+            }
+        })
+        .collect()
+}
+
+fn match_path(pattern: &[Segment], path: &str) -> Option<Params> {
+    let mut rest = path.strip_prefix('/')?;
+    let mut params = Params::default();
+    for (i, segment) in pattern.iter().enumerate() {
+        if let Segment::Wildcard(name) = segment {
+            params.entries.push((name.clone(), percent_decode(rest)?));
+            return Some(params);
+        }
+        let (part, tail) = match rest.split_once('/') {
+            Some((part, tail)) => (part, Some(tail)),
+            None => (rest, None),
+        };
+        match segment {
+            Segment::Literal(literal) if literal == part => {}
+            Segment::Param(name) if !part.is_empty() => {
+                params.entries.push((name.clone(), percent_decode(part)?));
+            }
+            _ => return None,
+        }
+        match tail {
+            Some(tail) => rest = tail,
+            // The path has run out: it matches if the pattern has too, or
+            // only a wildcard is left to match nothing.
+            None => {
+                return match pattern.get(i + 1) {
+                    None => Some(params),
+                    Some(Segment::Wildcard(name)) => {
+                        params.entries.push((name.clone(), String::new()));
+                        Some(params)
+                    }
+                    Some(_) => None,
+                };
+            }
+        }
+    }
+    // The pattern ended before the path did.
+    None
+}
+
+/// Decodes `%XX` escapes in a path. Returns None if an escape is malformed
+/// or the result is not UTF-8.
+pub(crate) fn percent_decode(s: &str) -> Option<String> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = s.get(i + 1..i + 3)?;
+            if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(pattern: &str, path: &str) -> Option<Vec<(String, String)>> {
+        let pattern = try_parse_pattern(pattern).unwrap();
+        match_path(&pattern, path).map(|params| {
+            params
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect()
+        })
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Option<Vec<(String, String)>> {
+        Some(
+            pairs
+                .iter()
+                .map(|&(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+        )
+    }
+
+    /// A handler that names itself in a Route header.
+    fn answer(name: &'static str) -> impl Fn(&Request) -> Response + Send + Sync + 'static {
+        move |_: &Request| Response::text(StatusCode::Ok, "OK").with_header("Route", name)
+    }
+
+    fn send(router: &Router, method: &str, path: &str) -> Response {
+        let raw = format!("{} {} HTTP/1.1\r\nHost: a\r\n\r\n", method, path);
+        let mut request = Request::read_from(&mut raw.as_bytes()).unwrap();
+        router.handle(&mut request)
+    }
+
+    fn route_of(response: &Response) -> Option<&str> {
+        response.headers().get("Route")
+    }
+
+    #[test]
+    fn matches_literals_and_captures_parameters() {
+        assert_eq!(params("/", "/"), pairs(&[]));
+        assert_eq!(params("/users", "/users"), pairs(&[]));
+        assert_eq!(params("/users", "/users/"), None);
+        assert_eq!(params("/users", "/user"), None);
+        assert_eq!(
+            params("/users/:id/posts/:post", "/users/42/posts/7"),
+            pairs(&[("id", "42"), ("post", "7")])
+        );
+        assert_eq!(
+            params("/users/:id", "/users/a%20b"),
+            pairs(&[("id", "a b")])
+        );
+        assert_eq!(params("/users/:id", "/users/"), None);
+        assert_eq!(params("/users/:id", "/users/42/posts"), None);
+        assert_eq!(params("/users/:id", "/users/%zz"), None);
+    }
+
+    #[test]
+    fn wildcards_capture_the_rest_of_the_path() {
+        assert_eq!(
+            params("/static/*path", "/static/css/site.css"),
+            pairs(&[("path", "css/site.css")])
+        );
+        assert_eq!(params("/static/*path", "/static/"), pairs(&[("path", "")]));
+        assert_eq!(params("/static/*path", "/static"), pairs(&[("path", "")]));
+        assert_eq!(params("/*path", "/"), pairs(&[("path", "")]));
+        assert_eq!(params("/static/*path", "/assets/a"), None);
+    }
+
+    #[test]
+    fn rejects_malformed_patterns() {
+        let error = |pattern| try_parse_pattern(pattern).err().unwrap();
+        assert_eq!(
+            error("users"),
+            "route pattern \"users\" must start with '/'"
+        );
+        assert_eq!(error("/users/:"), "unnamed parameter in route \"/users/:\"");
+        assert_eq!(
+            error("/static/*"),
+            "unnamed wildcard in route \"/static/*\""
+        );
+        assert_eq!(error("/*path/a"), "wildcard must end route \"/*path/a\"");
+        assert!(check_pattern("/users/:id/*rest").is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "must start with '/'")]
+    fn route_panics_on_a_malformed_pattern() {
+        let _ = Router::new().get("users", answer("users"));
+    }
+
+    #[test]
+    fn first_matching_route_wins() {
+        let router = Router::new()
+            .get("/users/me", answer("me"))
+            .get("/users/:id", answer("user"))
+            .get("/users/admin", answer("unreachable"));
+        assert_eq!(route_of(&send(&router, "GET", "/users/me")), Some("me"));
+        assert_eq!(
+            route_of(&send(&router, "GET", "/users/admin")),
+            Some("user")
+        );
+    }
+
+    #[test]
+    fn handlers_see_the_captured_parameters() {
+        let router = Router::new().get("/users/:id", |request: &Request| {
+            let id = request.params().get("id").unwrap_or("none").to_string();
+            Response::text(StatusCode::Ok, "OK").with_header("Route", id)
+        });
+        assert_eq!(route_of(&send(&router, "GET", "/users/42")), Some("42"));
+    }
+
+    #[test]
+    fn get_routes_answer_head() {
+        let router = Router::new().get("/", answer("index"));
+        assert_eq!(route_of(&send(&router, "HEAD", "/")), Some("index"));
+    }
+
+    #[test]
+    fn wrong_method_is_405_with_allow() {
+        let router = Router::new()
+            .get("/items", answer("list"))
+            .post("/items", answer("create"))
+            .put("/items/:id", answer("update"))
+            .delete("/items/:id", answer("delete"));
+
+        let response = send(&router, "DELETE", "/items");
+        assert_eq!(response.status(), StatusCode::MethodNotAllowed);
+        assert_eq!(response.headers().get("Allow"), Some("GET, POST, HEAD"));
+
+        let response = send(&router, "GET", "/items/3");
+        assert_eq!(response.status(), StatusCode::MethodNotAllowed);
+        assert_eq!(response.headers().get("Allow"), Some("PUT, DELETE"));
+    }
+
+    #[test]
+    fn unmatched_paths_go_to_the_fallback() {
+        let router = Router::new().get("/", answer("index"));
+        assert_eq!(
+            send(&router, "GET", "/missing").status(),
+            StatusCode::NotFound
+        );
+
+        let router = router.fallback(answer("fallback"));
+        assert_eq!(
+            route_of(&send(&router, "POST", "/missing")),
+            Some("fallback")
+        );
+        assert_eq!(route_of(&send(&router, "GET", "/")), Some("index"));
+    }
+}
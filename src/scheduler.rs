@@ -0,0 +1,421 @@
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Condvar, Mutex, MutexGuard, RwLock, TryLockError,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::Task;
+
+/// How many times a worker tries for the channel's receiver, yielding in
+/// between, before it waits to be told the receiver was released.
+const SPINS: usize = 8;
+
+/// How a ThreadPool hands queued jobs to its workers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheduler {
+    /// One shared channel that every worker receives from.
+    #[default]
+    Channel,
+    /// One deque per worker. Idle workers steal from the others, and jobs
+    /// submitted from inside a worker go to that worker's own deque.
+    WorkStealing,
+}
+
+/// The queue between `ThreadPool::execute` and the workers.
+pub(crate) enum JobQueue {
+    Channel {
+        sender: RwLock<Option<mpsc::Sender<Task>>>,
+        receiver: Mutex<mpsc::Receiver<Task>>,
+        /// The number of workers waiting for `receiver`. They wait on
+        /// `turn_over` rather than on the lock, so the wait can time out.
+        waiting: Mutex<usize>,
+        turn_over: Condvar,
+    },
+    WorkStealing(Deques),
+}
+
+impl JobQueue {
+    /// Creates a queue for a pool of at most workers threads.
+    pub(crate) fn new(scheduler: Scheduler, workers: usize) -> JobQueue {
+        match scheduler {
+            Scheduler::Channel => {
+                let (sender, receiver) = mpsc::channel();
+                JobQueue::Channel {
+                    sender: RwLock::new(Some(sender)),
+                    receiver: Mutex::new(receiver),
+                    waiting: Mutex::new(0),
+                    turn_over: Condvar::new(),
+                }
+            }
+            Scheduler::WorkStealing => JobQueue::WorkStealing(Deques::new(workers)),
+        }
+    }
+
+    pub(crate) fn push(&self, task: Task) {
+        match self {
+            JobQueue::Channel { sender, .. } => {
+                sender.read().unwrap().as_ref().unwrap().send(task).unwrap();
+            }
+            JobQueue::WorkStealing(deques) => deques.push(task),
+        }
+    }
+
+    /// Waits for a job for worker id, giving up after timeout if one is set.
+    ///
+    /// Returns `Disconnected` once the queue is closed and empty.
+    pub(crate) fn pop(
+        &self,
+        id: usize,
+        timeout: Option<Duration>,
+    ) -> Result<Task, RecvTimeoutError> {
+        match self {
+            JobQueue::Channel {
+                receiver,
+                waiting,
+                turn_over,
+                ..
+            } => {
+                let deadline = timeout.map(|timeout| Instant::now() + timeout);
+                let receiver = loop {
+                    if let Some(receiver) = spin_lock(receiver) {
+                        break receiver;
+                    }
+                    let mut waiters = waiting.lock().unwrap();
+                    // Look again now that waiting is held: the receiver is
+                    // released before waiting is taken to notify, so a
+                    // release can't slip in between this and the wait.
+                    if let Some(receiver) = try_lock(receiver) {
+                        break receiver;
+                    }
+                    *waiters += 1;
+                    waiters = match deadline {
+                        Some(deadline) => {
+                            let now = Instant::now();
+                            if now >= deadline {
+                                *waiters -= 1;
+                                return Err(RecvTimeoutError::Timeout);
+                            }
+                            turn_over.wait_timeout(waiters, deadline - now).unwrap().0
+                        }
+                        None => turn_over.wait(waiters).unwrap(),
+                    };
+                    *waiters -= 1;
+                };
+
+                let result = match deadline {
+                    Some(deadline) => {
+                        receiver.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                    }
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                drop(receiver);
+                if *waiting.lock().unwrap() > 0 {
+                    turn_over.notify_one();
+                }
+                result
+            }
+            JobQueue::WorkStealing(deques) => deques.pop(id, timeout),
+        }
+    }
+
+    /// Stops accepting jobs. Workers still receive what is already queued.
+    pub(crate) fn close(&self) {
+        match self {
+            JobQueue::Channel { sender, .. } => drop(sender.write().unwrap().take()),
+            JobQueue::WorkStealing(deques) => deques.close(),
+        }
+    }
+
+    /// Removes every queued job without running it.
+    pub(crate) fn drain(&self) -> Vec<Task> {
+        match self {
+            JobQueue::Channel { receiver, .. } => receiver.lock().unwrap().try_iter().collect(),
+            JobQueue::WorkStealing(deques) => deques.drain(),
+        }
+    }
+
+    /// Marks the calling thread as worker id, so its own pushes stay local.
+    pub(crate) fn register(&self, id: usize) {
+        if let JobQueue::WorkStealing(deques) = self {
+            CURRENT.with(|current| current.set(Some((deques.key(), id))));
+        }
+    }
+}
+
+/// Locks mutex if no one else holds it.
+fn try_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    match mutex.try_lock() {
+        Ok(guard) => Some(guard),
+        Err(TryLockError::WouldBlock) => None,
+        Err(TryLockError::Poisoned(e)) => panic!("{}", e),
+    }
+}
+
+/// Tries to lock mutex a few times, yielding in between. The receiver is
+/// usually released again quickly while jobs are coming in, and this is
+/// much cheaper than a round trip through `turn_over`.
+fn spin_lock<T>(mutex: &Mutex<T>) -> Option<MutexGuard<'_, T>> {
+    for _ in 0..SPINS {
+        if let Some(guard) = try_lock(mutex) {
+            return Some(guard);
+        }
+        thread::yield_now();
+    }
+    None
+}
+
+thread_local! {
+    /// The deques and slot of the worker running on this thread, if any.
+    static CURRENT: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// Per-worker deques for the work-stealing scheduler.
+///
+/// A worker takes from the front of its own deque and steals from the back
+/// of the others.
+pub(crate) struct Deques {
+    locals: Vec<Mutex<VecDeque<Task>>>,
+    next: AtomicUsize,
+    pending: AtomicUsize,
+    sleeping: AtomicUsize,
+    closed: AtomicBool,
+    lock: Mutex<()>,
+    available: Condvar,
+}
+
+impl Deques {
+    fn new(workers: usize) -> Deques {
+        Deques {
+            locals: (0..workers).map(|_| Mutex::new(VecDeque::new())).collect(),
+            next: AtomicUsize::new(0),
+            pending: AtomicUsize::new(0),
+            sleeping: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            lock: Mutex::new(()),
+            available: Condvar::new(),
+        }
+    }
+
+    fn key(&self) -> usize {
+        self as *const Deques as usize
+    }
+
+    fn push(&self, task: Task) {
+        let local = CURRENT
+            .with(Cell::get)
+            .and_then(|(key, id)| (key == self.key()).then_some(id));
+        let slot =
+            local.unwrap_or_else(|| self.next.fetch_add(1, Ordering::Relaxed)) % self.locals.len();
+        // Count the job first so `find` never takes more jobs than are counted.
+        self.pending.fetch_add(1, Ordering::SeqCst);
+        self.locals[slot].lock().unwrap().push_back(task);
+
+        if self.sleeping.load(Ordering::SeqCst) > 0 {
+            let _guard = self.lock.lock().unwrap();
+            self.available.notify_one();
+        }
+    }
+
+    fn pop(&self, id: usize, timeout: Option<Duration>) -> Result<Task, RecvTimeoutError> {
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        loop {
+            if let Some(task) = self.find(id) {
+                return Ok(task);
+            }
+
+            let guard = self.lock.lock().unwrap();
+            self.sleeping.fetch_add(1, Ordering::SeqCst);
+            let result = if self.pending.load(Ordering::SeqCst) > 0 {
+                Ok(())
+            } else if self.closed.load(Ordering::SeqCst) {
+                Err(RecvTimeoutError::Disconnected)
+            } else {
+                match deadline {
+                    Some(deadline) => {
+                        let remaining = deadline.saturating_duration_since(Instant::now());
+                        let (_guard, wait) = self.available.wait_timeout(guard, remaining).unwrap();
+                        if wait.timed_out() && self.pending.load(Ordering::SeqCst) == 0 {
+                            Err(RecvTimeoutError::Timeout)
+                        } else {
+                            Ok(())
+                        }
+                    }
+                    None => {
+                        let _guard = self.available.wait(guard).unwrap();
+                        Ok(())
+                    }
+                }
+            };
+            self.sleeping.fetch_sub(1, Ordering::SeqCst);
+            result?;
+        }
+    }
+
+    /// Takes a job from the worker's own deque, or steals one from another.
+    fn find(&self, id: usize) -> Option<Task> {
+        let n = self.locals.len();
+        let own = id % n;
+        let task = self.locals[own].lock().unwrap().pop_front().or_else(|| {
+            (1..n).find_map(|offset| self.locals[(own + offset) % n].lock().unwrap().pop_back())
+        })?;
+        self.pending.fetch_sub(1, Ordering::SeqCst);
+        Some(task)
+    }
+
+    fn drain(&self) -> Vec<Task> {
+        let mut jobs = Vec::new();
+        for local in &self.locals {
+            let mut local = local.lock().unwrap();
+            self.pending.fetch_sub(local.len(), Ordering::SeqCst);
+            jobs.extend(local.drain(..));
+        }
+        jobs
+    }
+
+    fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _guard = self.lock.lock().unwrap();
+        self.available.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{wait_until, Gate},
+        ThreadPool, ThreadPoolBuilder,
+    };
+    use std::{collections::HashSet, sync::Arc, thread};
+
+    fn elastic_pool(scheduler: Scheduler) -> ThreadPool {
+        ThreadPoolBuilder::new()
+            .min_threads(1)
+            .max_threads(4)
+            .keep_alive(Duration::from_millis(200))
+            .scheduler(scheduler)
+            .build()
+            .unwrap()
+    }
+
+    /// Checks the pool grows to its maximum under load, no further, and
+    /// shrinks back to its minimum once idle.
+    fn grows_and_retires(scheduler: Scheduler) {
+        let pool = elastic_pool(scheduler);
+        assert_eq!(pool.size(), 1);
+
+        let gate = Gate::new();
+        let running = Arc::new(AtomicUsize::new(0));
+        for _ in 0..6 {
+            let gate = gate.clone();
+            let running = Arc::clone(&running);
+            pool.execute(move || {
+                running.fetch_add(1, Ordering::SeqCst);
+                gate.wait();
+            });
+        }
+        wait_until("four jobs run", || running.load(Ordering::SeqCst) == 4);
+        assert_eq!(pool.size(), 4);
+        assert_eq!(pool.queued(), 2);
+
+        gate.open();
+        wait_until("every job runs", || running.load(Ordering::SeqCst) == 6);
+        // Idle workers retire together, not one keep-alive after another,
+        // which would take 600ms.
+        let idle_since = Instant::now();
+        wait_until("the pool shrinks", || pool.size() == 1);
+        assert!(
+            idle_since.elapsed() < Duration::from_millis(450),
+            "took {:?}",
+            idle_since.elapsed()
+        );
+
+        // It can grow again.
+        let handles: Vec<_> = (0..8).map(|i| pool.submit(move || i)).collect();
+        let sum: i32 = handles.into_iter().map(|h| h.join().unwrap()).sum();
+        assert_eq!(sum, 28);
+    }
+
+    #[test]
+    fn channel_pool_grows_and_retires() {
+        grows_and_retires(Scheduler::Channel);
+    }
+
+    #[test]
+    fn work_stealing_pool_grows_and_retires() {
+        grows_and_retires(Scheduler::WorkStealing);
+    }
+
+    #[test]
+    fn idle_workers_steal_jobs_queued_by_a_worker() {
+        let pool = Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(4)
+                .scheduler(Scheduler::WorkStealing)
+                .build()
+                .unwrap(),
+        );
+        // Jobs queued from inside a worker go to its own deque. They can
+        // only all run at once, alongside the job that queued them, if the
+        // other workers steal them.
+        let running = Arc::new(AtomicUsize::new(0));
+        let rendezvous = |running: &AtomicUsize| {
+            running.fetch_add(1, Ordering::SeqCst);
+            wait_until("four jobs run at once", || {
+                running.load(Ordering::SeqCst) == 4
+            });
+            thread::current().id()
+        };
+        let outer = pool.submit({
+            let pool = Arc::clone(&pool);
+            let running = Arc::clone(&running);
+            move || {
+                let inner: Vec<_> = (0..3)
+                    .map(|_| {
+                        let running = Arc::clone(&running);
+                        pool.submit(move || rendezvous(&running))
+                    })
+                    .collect();
+                let mut threads = vec![rendezvous(&running)];
+                threads.extend(inner.into_iter().map(|h| h.join().unwrap()));
+                threads
+            }
+        });
+        let threads: HashSet<_> = outer.join().unwrap().into_iter().collect();
+        assert_eq!(threads.len(), 4);
+    }
+
+    #[test]
+    fn every_job_runs_once() {
+        for scheduler in [Scheduler::Channel, Scheduler::WorkStealing] {
+            let pool = elastic_pool(scheduler);
+            let ran = Arc::new(AtomicUsize::new(0));
+            for _ in 0..1000 {
+                let ran = Arc::clone(&ran);
+                pool.execute(move || {
+                    ran.fetch_add(1, Ordering::SeqCst);
+                });
+            }
+            drop(pool);
+            assert_eq!(ran.load(Ordering::SeqCst), 1000, "{:?}", scheduler);
+        }
+    }
+
+    #[test]
+    fn fixed_pool_keeps_its_workers() {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(2)
+            .keep_alive(Duration::from_millis(1))
+            .build()
+            .unwrap();
+        pool.submit(|| ()).join().unwrap();
+        thread::sleep(Duration::from_millis(20));
+        assert_eq!(pool.size(), 2);
+    }
+}
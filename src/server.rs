@@ -0,0 +1,294 @@
+use std::{
+    error::Error,
+    fmt, io,
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{Arc, RwLock},
+    thread,
+    time::Duration,
+};
+
+use crate::{
+    serve_connection, Handler, KeepAlive, Level, Limits, Response, StatusCode, ThreadPool,
+};
+
+/// The shortest and longest pauses after a failed accept.
+const MIN_BACKOFF: Duration = Duration::from_millis(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(1);
+
+/// An error met while serving, passed to the server's error handler.
+///
+/// None of these stop the server except `Bind`, which `Server::bind`
+/// returns.
+#[derive(Debug)]
+pub enum ServerError {
+    /// The listening socket could not be bound.
+    Bind(io::Error),
+    /// Accepting a connection failed, for instance because the process ran
+    /// out of file descriptors. The server backs off and retries.
+    Accept(io::Error),
+    /// Reading from or writing to a connection failed for a reason other
+    /// than the client hanging up or going idle. The connection is closed.
+    Io(io::Error),
+    /// The handler panicked. The client was sent a 500 and the connection
+    /// closed.
+    HandlerPanicked(Option<String>),
+    /// The pool's queue was full. The client was sent a 503.
+    Overloaded,
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Bind(e) => write!(f, "failed to bind: {}", e),
+            ServerError::Accept(e) => write!(f, "failed to accept a connection: {}", e),
+            ServerError::Io(e) => write!(f, "connection error: {}", e),
+            ServerError::HandlerPanicked(Some(msg)) => write!(f, "handler panicked: {}", msg),
+            ServerError::HandlerPanicked(None) => f.write_str("handler panicked"),
+            ServerError::Overloaded => f.write_str("job queue is full, rejected a connection"),
+        }
+    }
+}
+
+impl Error for ServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ServerError::Bind(e) | ServerError::Accept(e) | ServerError::Io(e) => Some(e),
+            ServerError::HandlerPanicked(_) | ServerError::Overloaded => None,
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(e: io::Error) -> ServerError {
+        ServerError::Io(e)
+    }
+}
+
+type ErrorHandler = dyn Fn(&ServerError) + Send + Sync;
+
+/// Accepts connections on one or more listeners and serves each one on a
+/// ThreadPool.
+///
+/// Errors are passed to the error handler and the server carries on. By
+/// default they are written to stderr.
+///
+/// The handler and settings can be replaced while the server runs through
+/// a ServerHandle.
+pub struct Server {
+    listeners: Vec<TcpListener>,
+    pool: ThreadPool,
+    serving: Arc<RwLock<Arc<Serving>>>,
+    on_error: Arc<ErrorHandler>,
+}
+
+/// What connections are served with. Each connection keeps the Serving
+/// that was current when it was accepted.
+#[derive(Clone)]
+struct Serving {
+    handler: Arc<dyn Handler>,
+    limits: Limits,
+    keep_alive: KeepAlive,
+}
+
+/// Replaces what a running Server serves new connections with.
+///
+/// Connections already open carry on with what they started with until
+/// they close, so a change never interrupts a request.
+#[derive(Clone)]
+pub struct ServerHandle {
+    serving: Arc<RwLock<Arc<Serving>>>,
+}
+
+impl ServerHandle {
+    pub fn set_handler<H: Handler + 'static>(&self, handler: H) {
+        update(&self.serving, |serving| serving.handler = Arc::new(handler));
+    }
+
+    pub fn set_limits(&self, limits: Limits) {
+        update(&self.serving, |serving| serving.limits = limits);
+    }
+
+    pub fn set_keep_alive(&self, keep_alive: KeepAlive) {
+        update(&self.serving, |serving| serving.keep_alive = keep_alive);
+    }
+
+    /// Replaces the handler and settings together, so no connection is
+    /// served with a mix of old and new.
+    pub fn replace<H: Handler + 'static>(&self, handler: H, limits: Limits, keep_alive: KeepAlive) {
+        update(&self.serving, |serving| {
+            *serving = Serving {
+                handler: Arc::new(handler),
+                limits,
+                keep_alive,
+            }
+        });
+    }
+}
+
+/// Swaps in a changed copy of the current Serving, leaving the old one to
+/// the connections that hold it.
+fn update(serving: &RwLock<Arc<Serving>>, f: impl FnOnce(&mut Serving)) {
+    let mut current = serving.write().unwrap();
+    let mut next = Serving::clone(&current);
+    f(&mut next);
+    *current = Arc::new(next);
+}
+
+impl Server {
+    /// Binds a listener to addr and makes a Server that serves its
+    /// connections with handler on pool.
+    pub fn bind<A, H>(addr: A, pool: ThreadPool, handler: H) -> Result<Server, ServerError>
+    where
+        A: ToSocketAddrs,
+        H: Handler + 'static,
+    {
+        let listener = TcpListener::bind(addr).map_err(ServerError::Bind)?;
+        Ok(Server::from_listener(listener, pool, handler))
+    }
+
+    /// Makes a Server from a listener that is already bound.
+    pub fn from_listener<H>(listener: TcpListener, pool: ThreadPool, handler: H) -> Server
+    where
+        H: Handler + 'static,
+    {
+        Server {
+            listeners: vec![listener],
+            pool,
+            serving: Arc::new(RwLock::new(Arc::new(Serving {
+                handler: Arc::new(handler),
+                limits: Limits::default(),
+                keep_alive: KeepAlive::default(),
+            }))),
+            on_error: Arc::new(|e: &ServerError| eprintln!("[{:<5}] {}", Level::Warn, e)),
+        }
+    }
+
+    /// Binds another listener to addr, whose connections are served the
+    /// same way.
+    pub fn listen<A: ToSocketAddrs>(mut self, addr: A) -> Result<Server, ServerError> {
+        let listener = TcpListener::bind(addr).map_err(ServerError::Bind)?;
+        self.listeners.push(listener);
+        Ok(self)
+    }
+
+    /// Sets the limits requests are parsed with.
+    pub fn limits(self, limits: Limits) -> Server {
+        update(&self.serving, |serving| serving.limits = limits);
+        self
+    }
+
+    /// Sets the persistent connection settings.
+    pub fn keep_alive(self, keep_alive: KeepAlive) -> Server {
+        update(&self.serving, |serving| serving.keep_alive = keep_alive);
+        self
+    }
+
+    /// Sets the function errors are passed to, replacing the default of
+    /// logging each one to stderr as a warning. It is called from the
+    /// accepting thread and from workers.
+    pub fn on_error<F>(mut self, f: F) -> Server
+    where
+        F: Fn(&ServerError) + Send + Sync + 'static,
+    {
+        self.on_error = Arc::new(f);
+        self
+    }
+
+    /// Returns a handle that can change the handler and settings while the
+    /// server runs.
+    pub fn handle(&self) -> ServerHandle {
+        ServerHandle {
+            serving: Arc::clone(&self.serving),
+        }
+    }
+
+    /// Returns the pool connections are served on, for instance to run
+    /// timers on.
+    pub fn pool(&self) -> &ThreadPool {
+        &self.pool
+    }
+
+    /// Returns the address of the first listener.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listeners[0].local_addr()
+    }
+
+    /// Returns the addresses of every listener, in the order they were
+    /// bound.
+    pub fn local_addrs(&self) -> io::Result<Vec<SocketAddr>> {
+        self.listeners.iter().map(TcpListener::local_addr).collect()
+    }
+
+    /// Accepts and serves connections. This never returns.
+    ///
+    /// Each listener after the first gets its own accepting thread. After
+    /// an accept error that isn't specific to one connection, the server
+    /// waits before trying again on that listener, doubling the wait on
+    /// each failure in a row up to a second.
+    pub fn run(&self) -> ! {
+        thread::scope(|scope| {
+            for listener in &self.listeners[1..] {
+                scope.spawn(move || self.accept_loop(listener));
+            }
+            self.accept_loop(&self.listeners[0])
+        })
+    }
+
+    fn accept_loop(&self, listener: &TcpListener) -> ! {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    backoff = MIN_BACKOFF;
+                    self.dispatch(stream);
+                }
+                // The client gave up before we got to it.
+                Err(e) if connection_error(&e) => {}
+                Err(e) => {
+                    (self.on_error)(&ServerError::Accept(e));
+                    thread::sleep(backoff);
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                }
+            }
+        }
+    }
+
+    fn dispatch(&self, stream: TcpStream) {
+        let overflow = stream.try_clone();
+        let serving = Arc::clone(&self.serving.read().unwrap());
+        let on_error = Arc::clone(&self.on_error);
+
+        let job = move || {
+            let Serving {
+                handler,
+                limits,
+                keep_alive,
+            } = &*serving;
+            if let Err(e) = serve_connection(stream, &**handler, limits, keep_alive) {
+                on_error(&e);
+            }
+        };
+        if self.pool.try_execute(job).is_err() {
+            (self.on_error)(&ServerError::Overloaded);
+            if let Ok(mut stream) = overflow {
+                let response = Response::new(StatusCode::ServiceUnavailable)
+                    .with_header("Connection", "close");
+                if let Err(e) = response.write_to(&mut stream) {
+                    (self.on_error)(&ServerError::Io(e));
+                }
+            }
+        }
+    }
+}
+
+/// Returns true if an accept error concerns only the connection being
+/// accepted, so there is no need to back off.
+fn connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+    )
+}
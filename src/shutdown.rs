@@ -0,0 +1,183 @@
+use std::{error::Error, fmt};
+
+/// What happens to queued jobs when a ThreadPool shuts down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ShutdownMode {
+    /// Run every queued job before the workers exit.
+    #[default]
+    Drain,
+    /// Discard queued jobs and wait only for the ones already running.
+    Cancel,
+}
+
+/// Returned by `ThreadPool::shutdown` when workers are still busy at the deadline.
+///
+/// Jobs still queued at the deadline are discarded. The busy workers are
+/// left to finish their current job in the background.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShutdownError {
+    busy: Vec<usize>,
+    discarded: usize,
+}
+
+impl ShutdownError {
+    pub(crate) fn new(busy: Vec<usize>, discarded: usize) -> ShutdownError {
+        ShutdownError { busy, discarded }
+    }
+
+    /// The ids of the workers that were still running a job.
+    pub fn busy_workers(&self) -> &[usize] {
+        &self.busy
+    }
+
+    /// The number of queued jobs that were discarded, by a Cancel shutdown
+    /// or at the deadline.
+    pub fn discarded(&self) -> usize {
+        self.discarded
+    }
+}
+
+impl fmt::Display for ShutdownError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "shutdown deadline passed with workers {:?} still busy",
+            self.busy
+        )?;
+        if self.discarded > 0 {
+            write!(f, " and {} queued jobs discarded", self.discarded)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error for ShutdownError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{wait_until, Gate, PATIENCE},
+        ThreadPool,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+
+    /// Queues `count` jobs behind one that holds the only worker until the
+    /// gate opens, counting the queued jobs that run.
+    fn blocked_pool(count: usize) -> (ThreadPool, Gate, Arc<AtomicUsize>) {
+        let pool = ThreadPool::new(1);
+        let gate = Gate::new();
+        let ran = Arc::new(AtomicUsize::new(0));
+        pool.execute({
+            let gate = gate.clone();
+            move || gate.wait()
+        });
+        wait_until("the blocking job runs", || pool.queued() == 0);
+        for _ in 0..count {
+            let ran = Arc::clone(&ran);
+            pool.execute(move || {
+                ran.fetch_add(1, Ordering::SeqCst);
+            });
+        }
+        (pool, gate, ran)
+    }
+
+    /// Opens the gate from another thread once the shutdown is under way.
+    fn open_later(gate: &Gate) -> thread::JoinHandle<()> {
+        let gate = gate.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            gate.open();
+        })
+    }
+
+    #[test]
+    fn drain_runs_queued_jobs() {
+        let (pool, gate, ran) = blocked_pool(5);
+        let opener = open_later(&gate);
+        assert_eq!(pool.shutdown(ShutdownMode::Drain, PATIENCE), Ok(()));
+        opener.join().unwrap();
+        assert_eq!(ran.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn cancel_waits_only_for_running_jobs() {
+        let (pool, gate, ran) = blocked_pool(5);
+        let opener = open_later(&gate);
+        assert_eq!(pool.shutdown(ShutdownMode::Cancel, PATIENCE), Ok(()));
+        opener.join().unwrap();
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn deadline_reports_busy_workers_and_discarded_jobs() {
+        let (pool, gate, ran) = blocked_pool(2);
+        let error = pool
+            .shutdown(ShutdownMode::Drain, Duration::from_millis(50))
+            .unwrap_err();
+        gate.open();
+        assert_eq!(error.busy_workers(), [0]);
+        assert_eq!(error.discarded(), 2);
+        assert_eq!(
+            error.to_string(),
+            "shutdown deadline passed with workers [0] still busy and 2 queued jobs discarded"
+        );
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn display_leaves_out_a_discarded_count_of_zero() {
+        let error = ShutdownError::new(vec![1, 3], 0);
+        assert_eq!(
+            error.to_string(),
+            "shutdown deadline passed with workers [1, 3] still busy"
+        );
+    }
+
+    #[test]
+    fn dropping_the_pool_drains_it() {
+        let (pool, gate, ran) = blocked_pool(5);
+        let opener = open_later(&gate);
+        drop(pool);
+        opener.join().unwrap();
+        assert_eq!(ran.load(Ordering::SeqCst), 5);
+    }
+
+    #[test]
+    fn cancel_counts_every_discarded_job() {
+        let pool = ThreadPool::new(4);
+        let (started, running) = mpsc::channel();
+        let (unblock, blocked) = mpsc::channel::<()>();
+        let blocked = Arc::new(Mutex::new(blocked));
+        for _ in 0..4 {
+            let started = started.clone();
+            let blocked = Arc::clone(&blocked);
+            pool.execute(move || {
+                started.send(()).unwrap();
+                let _ = blocked.lock().unwrap().recv();
+            });
+        }
+        for _ in 0..4 {
+            running.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+        for _ in 0..3 {
+            pool.execute(|| panic!("discarded jobs don't run"));
+        }
+
+        let error = pool
+            .shutdown(ShutdownMode::Cancel, Duration::from_millis(50))
+            .unwrap_err();
+        drop(unblock);
+        assert_eq!(error.discarded(), 3);
+        let mut busy = error.busy_workers().to_vec();
+        busy.sort();
+        assert_eq!(busy, [0, 1, 2, 3]);
+    }
+}
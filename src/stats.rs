@@ -0,0 +1,263 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// A snapshot of what a ThreadPool is doing, returned by `ThreadPool::stats`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Jobs waiting for a worker.
+    pub queued: usize,
+    /// Worker threads currently running.
+    pub workers: usize,
+    /// Workers running a job.
+    pub busy: usize,
+    /// Workers waiting for a job.
+    pub idle: usize,
+    /// Jobs that have finished running, including those that panicked.
+    pub completed: u64,
+    /// Jobs that panicked.
+    pub panicked: u64,
+    /// How long jobs waited in the queue before a worker picked them up.
+    pub queue_wait: Histogram,
+    /// How long jobs took to run.
+    pub run_time: Histogram,
+}
+
+/// Number of buckets in a Histogram. The last bucket holds everything
+/// from about 36 minutes up.
+const BUCKETS: usize = 32;
+
+/// A latency histogram with power-of-two microsecond buckets.
+///
+/// Bucket 0 counts durations under 1µs and bucket i counts durations from
+/// 2^(i-1)µs up to 2^i µs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    buckets: [u64; BUCKETS],
+    sum_micros: u64,
+    max_micros: u64,
+}
+
+impl Histogram {
+    /// Number of durations recorded.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+
+    /// Mean of the recorded durations, or zero if there are none.
+    pub fn mean(&self) -> Duration {
+        match self.count() {
+            0 => Duration::ZERO,
+            count => Duration::from_micros(self.sum_micros / count),
+        }
+    }
+
+    /// Longest recorded duration.
+    pub fn max(&self) -> Duration {
+        Duration::from_micros(self.max_micros)
+    }
+
+    /// Upper bound of the bucket holding the given percentile, from 0 to 100.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        let count = self.count();
+        if count == 0 {
+            return Duration::ZERO;
+        }
+        let rank = ((percentile.clamp(0.0, 100.0) / 100.0) * count as f64).ceil() as u64;
+        let mut seen = 0;
+        for (bucket, n) in self.buckets.iter().enumerate() {
+            seen += n;
+            if seen >= rank.max(1) {
+                return bucket_bound(bucket).min(self.max());
+            }
+        }
+        self.max()
+    }
+
+    /// Each bucket's upper bound together with how many durations fell in it.
+    pub fn buckets(&self) -> impl Iterator<Item = (Duration, u64)> + '_ {
+        self.buckets
+            .iter()
+            .enumerate()
+            .map(|(bucket, n)| (bucket_bound(bucket), *n))
+    }
+}
+
+fn bucket_bound(bucket: usize) -> Duration {
+    Duration::from_micros(1 << bucket)
+}
+
+/// Lock-free counters the workers update as they run jobs.
+#[derive(Default)]
+pub(crate) struct Counters {
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    queue_wait: AtomicHistogram,
+    run_time: AtomicHistogram,
+}
+
+impl Counters {
+    pub(crate) fn job_started(&self, waited: Duration) {
+        self.queue_wait.record(waited);
+    }
+
+    pub(crate) fn job_finished(&self, ran: Duration) {
+        self.completed.fetch_add(1, Ordering::Relaxed);
+        self.run_time.record(ran);
+    }
+
+    pub(crate) fn job_panicked(&self) {
+        self.panicked.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn completed(&self) -> u64 {
+        self.completed.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn panicked(&self) -> u64 {
+        self.panicked.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn queue_wait(&self) -> Histogram {
+        self.queue_wait.snapshot()
+    }
+
+    pub(crate) fn run_time(&self) -> Histogram {
+        self.run_time.snapshot()
+    }
+}
+
+#[derive(Default)]
+struct AtomicHistogram {
+    buckets: [AtomicU64; BUCKETS],
+    sum_micros: AtomicU64,
+    max_micros: AtomicU64,
+}
+
+impl AtomicHistogram {
+    fn record(&self, duration: Duration) {
+        let micros = u64::try_from(duration.as_micros()).unwrap_or(u64::MAX);
+        let bucket = (u64::BITS - micros.leading_zeros()) as usize;
+        self.buckets[bucket.min(BUCKETS - 1)].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros.fetch_add(micros, Ordering::Relaxed);
+        self.max_micros.fetch_max(micros, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> Histogram {
+        Histogram {
+            buckets: std::array::from_fn(|i| self.buckets[i].load(Ordering::Relaxed)),
+            sum_micros: self.sum_micros.load(Ordering::Relaxed),
+            max_micros: self.max_micros.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testing::{wait_until, Gate},
+        ThreadPool, ThreadPoolBuilder,
+    };
+
+    fn histogram(micros: &[u64]) -> Histogram {
+        let histogram = AtomicHistogram::default();
+        for &micros in micros {
+            histogram.record(Duration::from_micros(micros));
+        }
+        histogram.snapshot()
+    }
+
+    #[test]
+    fn counts_completed_and_panicked_jobs() {
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(2)
+            .panic_handler(|_| {})
+            .build()
+            .unwrap();
+        for _ in 0..3 {
+            pool.execute(|| {});
+        }
+        pool.execute(|| panic!("counted"));
+        wait_until("every job finishes", || pool.stats().completed == 4);
+
+        let stats = pool.stats();
+        assert_eq!(stats.panicked, 1);
+        assert_eq!(stats.queue_wait.count(), 4);
+        assert_eq!(stats.run_time.count(), 4);
+    }
+
+    #[test]
+    fn reports_busy_idle_and_queued() {
+        let pool = ThreadPool::new(3);
+        let gate = Gate::new();
+        for _ in 0..4 {
+            let gate = gate.clone();
+            pool.execute(move || gate.wait());
+        }
+        wait_until("every worker is busy", || pool.stats().busy == 3);
+
+        let stats = pool.stats();
+        assert_eq!(stats.workers, 3);
+        assert_eq!(stats.idle, 0);
+        assert_eq!(stats.queued, 1);
+        assert_eq!(stats.completed, 0);
+
+        gate.open();
+        wait_until("every job finishes", || pool.stats().completed == 4);
+        wait_until("the workers are idle", || pool.stats().idle == 3);
+        let stats = pool.stats();
+        assert_eq!((stats.busy, stats.queued), (0, 0));
+    }
+
+    #[test]
+    fn durations_land_in_power_of_two_buckets() {
+        let buckets: Vec<_> = histogram(&[0, 1, 2, 3, 4, 1000])
+            .buckets()
+            .filter(|&(_, n)| n > 0)
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                (Duration::from_micros(1), 1),
+                (Duration::from_micros(2), 1),
+                (Duration::from_micros(4), 2),
+                (Duration::from_micros(8), 1),
+                (Duration::from_micros(1024), 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn very_long_durations_share_the_last_bucket() {
+        let histogram = histogram(&[u64::MAX]);
+        let (bound, n) = histogram.buckets().last().unwrap();
+        assert_eq!((bound, n), (Duration::from_micros(1 << (BUCKETS - 1)), 1));
+        assert_eq!(histogram.max(), Duration::from_micros(u64::MAX));
+    }
+
+    #[test]
+    fn summarises_recorded_durations() {
+        let histogram = histogram(&[10, 20, 30, 40, 1000]);
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.mean(), Duration::from_micros(220));
+        assert_eq!(histogram.max(), Duration::from_micros(1000));
+        assert_eq!(histogram.percentile(0.0), Duration::from_micros(16));
+        assert_eq!(histogram.percentile(50.0), Duration::from_micros(32));
+        assert_eq!(histogram.percentile(80.0), Duration::from_micros(64));
+        // The last bucket's bound is past the largest duration.
+        assert_eq!(histogram.percentile(100.0), Duration::from_micros(1000));
+        assert_eq!(histogram.percentile(250.0), Duration::from_micros(1000));
+    }
+
+    #[test]
+    fn empty_histogram_reports_zero() {
+        let histogram = histogram(&[]);
+        assert_eq!(histogram.count(), 0);
+        assert_eq!(histogram.mean(), Duration::ZERO);
+        assert_eq!(histogram.max(), Duration::ZERO);
+        assert_eq!(histogram.percentile(99.0), Duration::ZERO);
+        assert!(histogram.buckets().all(|(_, n)| n == 0));
+    }
+}
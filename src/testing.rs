@@ -0,0 +1,47 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long tests wait for something that should happen almost at once.
+pub(crate) const PATIENCE: Duration = Duration::from_secs(5);
+
+/// Holds the jobs that wait on it until it is opened, or for PATIENCE at
+/// most, so a failing test can't leave a pool waiting forever.
+#[derive(Clone, Default)]
+pub(crate) struct Gate(Arc<(Mutex<bool>, Condvar)>);
+
+impl Gate {
+    pub(crate) fn new() -> Gate {
+        Gate::default()
+    }
+
+    pub(crate) fn wait(&self) {
+        let (open, opened) = &*self.0;
+        let open = open.lock().unwrap();
+        let _open = opened
+            .wait_timeout_while(open, PATIENCE, |open| !*open)
+            .unwrap();
+    }
+
+    pub(crate) fn open(&self) {
+        let (open, opened) = &*self.0;
+        *open.lock().unwrap() = true;
+        opened.notify_all();
+    }
+}
+
+/// Polls until condition holds, panicking if it takes longer than
+/// PATIENCE.
+pub(crate) fn wait_until(what: &str, mut condition: impl FnMut() -> bool) {
+    let deadline = Instant::now() + PATIENCE;
+    while !condition() {
+        assert!(
+            Instant::now() < deadline,
+            "timed out waiting until {}",
+            what
+        );
+        thread::sleep(Duration::from_millis(1));
+    }
+}
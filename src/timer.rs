@@ -0,0 +1,358 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    io,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::{Job, Shared};
+
+/// A handle to a job scheduled with `ThreadPool::execute_after` or
+/// `ThreadPool::execute_every`.
+///
+/// Dropping the handle does not cancel the job.
+#[derive(Debug, Clone)]
+pub struct TimerHandle {
+    state: Arc<TimerState>,
+}
+
+impl TimerHandle {
+    /// Stops the job from running again. A run already handed to a worker
+    /// still finishes.
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, atomic::Ordering::SeqCst);
+    }
+
+    /// Returns true if the job has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(atomic::Ordering::SeqCst)
+    }
+}
+
+#[derive(Debug, Default)]
+struct TimerState {
+    cancelled: AtomicBool,
+    /// Set while a run of a periodic job is queued or running, so slow runs
+    /// do not pile up.
+    running: AtomicBool,
+}
+
+/// The timers of a ThreadPool and the one thread that fires them.
+#[derive(Default)]
+pub(crate) struct Timers {
+    queue: Mutex<TimerQueue>,
+    wake: Condvar,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+#[derive(Default)]
+struct TimerQueue {
+    entries: BinaryHeap<Entry>,
+    next_seq: u64,
+    stopped: bool,
+}
+
+struct Entry {
+    due: Instant,
+    seq: u64,
+    state: Arc<TimerState>,
+    kind: Kind,
+}
+
+enum Kind {
+    Once(Job),
+    Every {
+        interval: Duration,
+        job: Arc<dyn Fn() + Send + Sync + 'static>,
+    },
+}
+
+// BinaryHeap is a max-heap, so entries compare in reverse to pop the
+// earliest due first. seq keeps timers due at the same instant in order.
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .due
+            .cmp(&self.due)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl Timers {
+    pub(crate) fn once(shared: &Arc<Shared>, delay: Duration, job: Job) -> io::Result<TimerHandle> {
+        Self::schedule(shared, delay, Kind::Once(job))
+    }
+
+    pub(crate) fn every(
+        shared: &Arc<Shared>,
+        interval: Duration,
+        job: Arc<dyn Fn() + Send + Sync + 'static>,
+    ) -> io::Result<TimerHandle> {
+        Self::schedule(shared, interval, Kind::Every { interval, job })
+    }
+
+    fn schedule(shared: &Arc<Shared>, delay: Duration, kind: Kind) -> io::Result<TimerHandle> {
+        let timers = &shared.timers;
+        timers.start(shared)?;
+
+        let state = Arc::new(TimerState::default());
+        // A delay too long to represent never ends, so there is nothing to
+        // queue.
+        if let Some(due) = Instant::now().checked_add(delay) {
+            let mut queue = timers.queue.lock().unwrap();
+            let seq = queue.next_seq;
+            queue.next_seq += 1;
+            queue.entries.push(Entry {
+                due,
+                seq,
+                state: Arc::clone(&state),
+                kind,
+            });
+            timers.wake.notify_one();
+        }
+        Ok(TimerHandle { state })
+    }
+
+    /// Spawns the timer thread on first use.
+    fn start(&self, shared: &Arc<Shared>) -> io::Result<()> {
+        let mut thread = self.thread.lock().unwrap();
+        if thread.is_none() {
+            *thread = Some(shared.config.spawn("timer", {
+                let shared = Arc::clone(shared);
+                move || timer_loop(&shared)
+            })?);
+        }
+        Ok(())
+    }
+
+    /// Drops every pending timer and waits for the timer thread to exit.
+    pub(crate) fn stop(&self) {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            queue.stopped = true;
+            queue.entries.clear();
+            self.wake.notify_one();
+        }
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn timer_loop(shared: &Arc<Shared>) {
+    let timers = &shared.timers;
+    let mut queue = timers.queue.lock().unwrap();
+    loop {
+        if queue.stopped {
+            return;
+        }
+        let now = Instant::now();
+        let due = match queue.entries.peek() {
+            Some(entry) => entry.due,
+            None => {
+                queue = timers.wake.wait(queue).unwrap();
+                continue;
+            }
+        };
+        if due > now {
+            queue = timers.wake.wait_timeout(queue, due - now).unwrap().0;
+            continue;
+        }
+
+        let entry = queue.entries.pop().unwrap();
+        if entry.state.cancelled.load(atomic::Ordering::SeqCst) {
+            continue;
+        }
+        match entry.kind {
+            Kind::Once(job) => {
+                drop(queue);
+                shared.execute(job);
+            }
+            Kind::Every { interval, job } => {
+                // Keep a steady rate, but skip ticks missed while we were
+                // behind. A next run too far off to represent never comes.
+                let next = entry
+                    .due
+                    .checked_add(interval)
+                    .filter(|&next| next > now)
+                    .or_else(|| now.checked_add(interval));
+                if let Some(next) = next {
+                    let seq = queue.next_seq;
+                    queue.next_seq += 1;
+                    queue.entries.push(Entry {
+                        due: next,
+                        seq,
+                        state: Arc::clone(&entry.state),
+                        kind: Kind::Every {
+                            interval,
+                            job: Arc::clone(&job),
+                        },
+                    });
+                }
+                drop(queue);
+
+                if !entry.state.running.swap(true, atomic::Ordering::SeqCst) {
+                    let running = Running(entry.state);
+                    shared.execute(Box::new(move || {
+                        let _running = running;
+                        job();
+                    }));
+                }
+            }
+        }
+        queue = timers.queue.lock().unwrap();
+    }
+}
+
+/// Clears the running flag of a periodic job once the run is over, even if
+/// it panicked or was discarded without running.
+struct Running(Arc<TimerState>);
+
+impl Drop for Running {
+    fn drop(&mut self) {
+        self.0.running.store(false, atomic::Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        testing::{wait_until, Gate, PATIENCE},
+        ShutdownMode, ThreadPool,
+    };
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            mpsc, Arc,
+        },
+        thread,
+        time::{Duration, Instant},
+    };
+
+    fn counter() -> Arc<AtomicUsize> {
+        Arc::new(AtomicUsize::new(0))
+    }
+
+    fn count(counter: &Arc<AtomicUsize>) -> impl Fn() + Send + Sync + 'static {
+        let counter = Arc::clone(counter);
+        move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn runs_a_job_after_its_delay() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        let start = Instant::now();
+        pool.execute_after(Duration::from_millis(30), move || tx.send(()).unwrap());
+        rx.recv_timeout(PATIENCE).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[test]
+    fn cancelled_jobs_never_run() {
+        let pool = ThreadPool::new(1);
+        let ran = counter();
+        let handle = pool.execute_after(Duration::from_millis(20), count(&ran));
+        handle.cancel();
+        assert!(handle.is_cancelled());
+
+        // Timers fire in order, so once a later one has run the
+        // cancelled one would have too.
+        let (tx, rx) = mpsc::channel();
+        pool.execute_after(Duration::from_millis(40), move || tx.send(()).unwrap());
+        rx.recv_timeout(PATIENCE).unwrap();
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn repeats_until_cancelled() {
+        let pool = ThreadPool::new(1);
+        let ran = counter();
+        let handle = pool.execute_every(Duration::from_millis(5), count(&ran));
+        wait_until("the job repeats", || ran.load(Ordering::SeqCst) >= 3);
+
+        handle.cancel();
+        // A run queued just before the cancel may still finish.
+        thread::sleep(Duration::from_millis(20));
+        let runs = ran.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(ran.load(Ordering::SeqCst), runs);
+    }
+
+    #[test]
+    fn slow_periodic_jobs_do_not_pile_up() {
+        let pool = ThreadPool::new(2);
+        let gate = Gate::new();
+        let started = counter();
+        let handle = pool.execute_every(Duration::from_millis(1), {
+            let gate = gate.clone();
+            let started = count(&started);
+            move || {
+                started();
+                gate.wait();
+            }
+        });
+        wait_until("the first run starts", || {
+            started.load(Ordering::SeqCst) == 1
+        });
+        thread::sleep(Duration::from_millis(30));
+        assert_eq!(started.load(Ordering::SeqCst), 1);
+        assert_eq!(pool.queued(), 0);
+
+        handle.cancel();
+        gate.open();
+    }
+
+    #[test]
+    fn timers_are_dropped_at_shutdown() {
+        let pool = ThreadPool::new(1);
+        let ran = counter();
+        pool.execute_after(Duration::from_millis(50), count(&ran));
+        pool.execute_every(Duration::from_millis(50), count(&ran));
+        pool.shutdown(ShutdownMode::Drain, PATIENCE).unwrap();
+
+        // Only this test holds the counter once both jobs are gone.
+        assert_eq!(Arc::strong_count(&ran), 1);
+        assert_eq!(ran.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn delays_too_long_to_represent_never_fire() {
+        let pool = ThreadPool::new(1);
+        pool.execute_after(Duration::MAX, || panic!("fired"));
+        pool.execute_every(Duration::MAX, || panic!("fired"));
+        // The timer thread is still usable, and dropping the pool doesn't
+        // find a poisoned queue.
+        let (tx, rx) = mpsc::channel();
+        pool.execute_after(Duration::ZERO, move || tx.send(()).unwrap());
+        rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        drop(pool);
+    }
+
+    #[test]
+    #[should_panic(expected = "non-zero interval")]
+    fn zero_interval_is_refused() {
+        ThreadPool::new(1).execute_every(Duration::ZERO, || {});
+    }
+}